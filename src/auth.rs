@@ -2,89 +2,159 @@
 //! password and usernames match (if existing user) and helps
 //! make sure the user puts in the correct password
 
-use argon2::Config;
 use rand::distributions::{Alphanumeric, DistString};
-use rusqlite::Connection;
-use zeroize::Zeroize;
+use rusqlite::{params, Connection};
+use std::path::Path;
 use std::str;
 
-use crate::user_inputs;
+use crate::error::Result;
+use crate::generator::Generator;
+use crate::password_change;
+use crate::schema::{self, Migration};
 
-/// Creates the database table for user authentication if they do not exist
+/// Migrations of the authentication database, see `schema`
+const MIGRATIONS: [Migration; 1] = [create_auth_tables];
+
+/// Schema version 1: the authentication table with KDF profiles and the password change tables
 /// ### Parameters
 /// - `conn` : Connection to a sqlite database (rusqlite object)
 /// ### Side-effects
-/// Creates tables if they do not exist. Quietly fails to do so if they already
-/// exists
-fn create_auth_tables(conn: &Connection) {
-    let query = "CREATE TABLE auth(
+/// Creates the tables. Databases made before schema versions may already have some
+/// of them, they get the KDF profile columns they are missing and their users keep
+/// the parameters of `Generator::new()`.
+fn create_auth_tables(conn: &Connection) -> rusqlite::Result<()> {
+    let query = "CREATE TABLE IF NOT EXISTS auth(
         username TEXT NOT NULL UNIQUE,
         password_hash TEXT,
         password_salt TEXT
     )";
+    conn.execute(query, ())?;
 
-    match conn.execute(query, ()) {
-        Ok(_) => (),
-        Err(_) => ()
-    };
+    let default = Generator::new();
+    let columns = [
+        format!("kdf_mem INTEGER NOT NULL DEFAULT {}", default.mem_cost()),
+        format!("kdf_time INTEGER NOT NULL DEFAULT {}", default.time_cost()),
+        format!("kdf_lanes INTEGER NOT NULL DEFAULT {}", default.lanes()),
+        format!("kdf_variant TEXT NOT NULL DEFAULT '{}'", default.variant().as_lowercase_str())
+    ];
+    let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+    schema::add_missing_columns(conn, "auth", &columns)?;
+
+    password_change::create_tables(conn)
+}
+
+/// Name of the authentication database file in the data directory
+pub const AUTH_DB: &str = "auth.db";
+
+/// Opens the authentication database and migrates it to the newest schema version
+/// ### Parameters
+/// - `filename`: path of the database file, usually `Paths::auth_db()`
+/// ### Returns
+/// Rusqlite Connection to the authentication database, or `Error::Database` if it
+/// cannot be opened or was written by a newer build
+/// ### Side-effect
+/// Makes connection with sqlite database, creates the file if it does not exist
+pub fn get_connection(filename: impl AsRef<Path>) -> Result<Connection> {
+    schema::open(filename.as_ref(), &MIGRATIONS)
 }
 
-/// Authenticates user based on the input username and password
+/// Checks whether the user has been set up
 /// ### Parameters
-/// - `username` : username of person to authenticate
-/// - `password` : passsword of the person to authenticate
+/// - `conn`    : Connection to the authentication database
+/// - `username`: username to look up
 /// ### Returns
-/// a boolean; true if the username and password match and false otherwise
+/// true if a user with the given username exists
+pub fn user_exists(conn: &Connection, username: &str) -> bool {
+    let query = "SELECT (username) from auth WHERE username = ?1";
+    conn.query_row(query, params![username], |row| row.get::<_, String>(0)).is_ok()
+}
+
+/// Creates a new user with the given password and the default KDF profile.
+/// Does not ask for confirmation.
+/// ### Parameters
+/// - `conn`    : Connection to the authentication database
+/// - `username`: username of the new user
+/// - `password`: password of the new user
+/// ### Returns
+/// false if the user already exists, true otherwise
 /// ### Side-effects
-/// Reads in password confirmation for new users from stdin.
-/// 
-/// Will zeroize the password if the password and confirm password do not match
-/// 
-/// Reads from and writes to authentication sqlite database.
-pub fn auth_user(username: &str, password: &mut str) -> bool {
-    let conn = Connection::open("auth.db").unwrap();
-    create_auth_tables(&conn);
-    let mut user_new = false;
+/// Writes to authentication sqlite database.
+pub fn add_user(conn: &Connection, username: &str, password: &str) -> bool {
+    add_user_with_profile(conn, username, password, &Generator::new())
+}
 
+/// Creates a new user with the given password and KDF profile. The profile is used
+/// for the stored password hash and every password generated for the user.
+/// For documentation of the other parameters see `add_user()`.
+pub fn add_user_with_profile(conn: &Connection, username: &str, password: &str, profile: &Generator) -> bool {
     // Try to put in username into unique table. If it fails, username exists
-    let query_add_user = format!("INSERT INTO auth (username) VALUES (\"{}\")", username);
-    match conn.execute(query_add_user.as_str(), ()) {
-        Ok(_) => {
-            user_new = true;
-        },
-        Err(_) => ()
+    let query_add_user = "INSERT INTO auth (username) VALUES (?1)";
+    if conn.execute(query_add_user, params![username]).is_err() {
+        return false;
     }
 
-    if user_new {
-        let mut confirm_pass = user_inputs::get_hidden_input_prompt("Confirm password: ");
-        if confirm_pass != password.to_string() {
-            confirm_pass.zeroize();
-            password.zeroize();
-
-            // clear username from auth table
-            let query = format!("DELETE FROM auth WHERE username = \"{}\"", username);
-            conn.execute(query.as_str(), ()).unwrap();
-            
-            return false;
-        }
-        let salt = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
-        let argon_config = Config::default();
-        let hash = argon2::hash_encoded(password.as_bytes(), salt.as_bytes(), &argon_config).unwrap();
-        let query = format!("INSERT OR REPLACE INTO auth (username, password_hash, password_salt) VALUES (\"{}\", \"{}\", \"{}\")", username, hash, salt);
-        conn.execute(query.as_str(), ()).unwrap();
-    } 
-
-    if !user_new {
-        let mut query = format!("SELECT (password_hash) from auth WHERE username = \"{}\"", username);
-        let stored_hash: String = conn.query_row(query.as_str(), (), |row| row.get(0)).unwrap();
-        query = format!("SELECT (password_salt) from auth WHERE username = \"{}\"", username);
-        let salt: String = conn.query_row(query.as_str(), (), |row| row.get(0)).unwrap();
-        let argon_config = Config::default();
-        let calculated_hash = argon2::hash_encoded(password.as_bytes(), salt.as_bytes(), &argon_config).unwrap();
-        if calculated_hash != stored_hash {
-            return false;
-        }
-    }
+    let salt = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+    let hash = argon2::hash_encoded(password.as_bytes(), salt.as_bytes(), &profile.config(32)).unwrap();
+    let query = "UPDATE auth SET password_hash = ?2, password_salt = ?3, kdf_mem = ?4, kdf_time = ?5,
+                 kdf_lanes = ?6, kdf_variant = ?7 WHERE username = ?1";
+    conn.execute(query, params![username, hash, salt, profile.mem_cost(), profile.time_cost(),
+                                profile.lanes(), profile.variant().as_lowercase_str()]).unwrap();
+    true
+}
 
-    return true;
-}
\ No newline at end of file
+/// Replaces the password of an existing user. The KDF profile is kept, so only
+/// the password changes the generated passwords.
+/// ### Parameters
+/// - `conn`    : Connection to the authentication database
+/// - `username`: username of the user
+/// - `password`: the new password
+/// ### Returns
+/// false if the user does not exist or has an invalid profile, true otherwise
+/// ### Side-effects
+/// Writes to authentication sqlite database.
+pub fn set_password(conn: &Connection, username: &str, password: &str) -> bool {
+    let profile = match read_profile(conn, username) {
+        Some(profile) => profile,
+        None => return false
+    };
+    let salt = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+    let hash = argon2::hash_encoded(password.as_bytes(), salt.as_bytes(), &profile.config(32)).unwrap();
+    let query = "UPDATE auth SET password_hash = ?2, password_salt = ?3 WHERE username = ?1";
+    conn.execute(query, params![username, hash, salt]).is_ok_and(|rows| rows == 1)
+}
+
+/// Reads the KDF profile of a user
+/// ### Parameters
+/// - `conn`    : Connection to the authentication database
+/// - `username`: username to look up
+/// ### Returns
+/// The generator with the user's Argon2 parameters, None if the user does not exist
+/// or the stored parameters are invalid
+pub fn read_profile(conn: &Connection, username: &str) -> Option<Generator> {
+    let query = "SELECT kdf_mem, kdf_time, kdf_lanes, kdf_variant from auth WHERE username = ?1";
+    let (mem_cost, time_cost, lanes, variant): (u32, u32, u32, String) = conn.query_row(query, params![username], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+    }).ok()?;
+    let variant = argon2::Variant::from_str(&variant).ok()?;
+    Generator::with_profile(mem_cost, time_cost, lanes, variant).ok()
+}
+
+/// Checks the password of an existing user
+/// ### Parameters
+/// - `conn`    : Connection to the authentication database
+/// - `username`: username of person to authenticate
+/// - `password`: passsword of the person to authenticate
+/// ### Returns
+/// true if the user exists and the password matches the stored hash
+/// ### Side-effects
+/// Reads from authentication sqlite database.
+pub fn verify_user(conn: &Connection, username: &str, password: &str) -> bool {
+    let query = "SELECT password_hash from auth WHERE username = ?1";
+    let stored_hash: String = match conn.query_row(query, params![username], |row| row.get(0)) {
+        Ok(hash) => hash,
+        Err(_) => return false
+    };
+
+    // the encoded hash carries the parameters it was made with
+    argon2::verify_encoded(&stored_hash, password.as_bytes()).unwrap_or(false)
+}
@@ -1,10 +1,12 @@
 //! Contains list of password-legal characters in their categories
-//! The `Key` struct and a helper method for main generator
+//! The `Key` and `Charset` structs and helper methods for main generator
+
+use crate::error::{Error, Result};
 
 /// A key that represents properties of generated password.
 /// The desired character groups can be marked as true and 
 /// undesired ones can be marked as false.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Key {
     pub upper: bool,
     pub lower: bool,
@@ -12,11 +14,11 @@ pub struct Key {
     pub sym: bool
 }
 
-impl Key {
+impl Default for Key {
     /// Returns the default `Key` where all of its values are `true`
     /// ### Returns
     /// A `Key` struct in default state i.e. with all its parameters set to `true`
-    pub fn default() -> Self {
+    fn default() -> Self {
         Key {
             upper: true,
             lower: true, 
@@ -24,7 +26,9 @@ impl Key {
             sym: true
         }
     }
+}
 
+impl Key {
     /// Converts 4 bool array into a key struct for making Key from user input
     /// ### Returns
     /// A `Key` struct in the state as specified
@@ -37,10 +41,42 @@ impl Key {
         }
     }
 
+    /// Makes a key from a string of class letters, e.g. `"ulns"` for all classes.
+    /// `u` is uppercase, `l` lowercase, `n` numbers and `s` symbols. Order does not matter.
+    /// ### Returns
+    /// The `Key` with the listed classes enabled or `Error::InvalidSpec` if the string
+    /// contains any other character
+    pub fn from_classes(classes: &str) -> Result<Self> {
+        let mut key = Key { upper: false, lower: false, num: false, sym: false };
+        for c in classes.chars() {
+            match c.to_ascii_lowercase() {
+                'u' => key.upper = true,
+                'l' => key.lower = true,
+                'n' => key.num = true,
+                's' => key.sym = true,
+                _ => return Err(Error::InvalidSpec(format!("unknown character class '{}'", c)))
+            }
+        }
+        Ok(key)
+    }
+
+    /// Makes a string of class letters from the `Key` struct. Inverse of `from_classes()`.
+    /// ### Returns
+    /// A `String` like `"ulns"` containing the letter of every enabled class
+    pub fn to_classes(self) -> String {
+        let mut classes = String::new();
+        if self.upper {classes.push('u')};
+        if self.lower {classes.push('l')};
+        if self.num {classes.push('n')};
+        if self.sym {classes.push('s')};
+
+        classes
+    }
+
     /// Makes a string from the `Key` struct
     /// ### Returns
     /// A `String` representation of the state of the `Key` struct
-    pub fn to_str(&self) -> String {
+    pub fn to_str(self) -> String {
         let mut string_return_val = String::new();
         if self.lower {string_return_val.push_str("lowercase ")};
         if self.upper {string_return_val.push_str("uppercase ")};
@@ -87,3 +123,113 @@ pub fn get_pass_building_chars(key: &Key) -> Vec<char> {
     return_vec
 }
 
+/// Characters that are easily mistaken for one another: 0/O and 1/l/I
+const LOOKALIKES: [char; 5] = ['0', 'O', '1', 'l', 'I'];
+
+/// Bit of an ASCII character in the masks of `Charset`
+fn ascii_bit(c: char) -> Result<u128> {
+    if c.is_ascii() {
+        Ok(1 << c as u32)
+    } else {
+        Err(Error::InvalidSpec(format!("'{}' is not a character passwords are made of", c)))
+    }
+}
+
+/// Characters of a mask in the order of `chars`
+fn mask_to_string(mask: u128, chars: &[char]) -> String {
+    chars.iter().filter(|c| mask & (1 << **c as u32) != 0).collect()
+}
+
+/// Narrows down the alphabets enabled by a `Key`. The default allows every
+/// character, so passwords made with it are the same as without a `Charset`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Charset {
+    /// ASCII mask of the symbols that may be used
+    symbols: u128,
+    /// ASCII mask of the characters that are never used
+    excluded: u128,
+    /// Never use the look-alike characters 0, O, 1, l and I
+    pub no_lookalikes: bool
+}
+
+impl Default for Charset {
+    /// Every symbol allowed and no character excluded
+    fn default() -> Self {
+        Charset {
+            symbols: SYMBOL.iter().fold(0, |mask, c| mask | 1 << *c as u32),
+            excluded: 0,
+            no_lookalikes: false
+        }
+    }
+}
+
+impl Charset {
+    /// Limits the symbols to the ones in the string, e.g. `"!#$%"` for sites that reject spaces and quotes
+    /// ### Returns
+    /// `Error::InvalidSpec` if the string contains something that is not a symbol
+    pub fn set_symbols(&mut self, symbols: &str) -> Result<()> {
+        let mut mask = 0;
+        for c in symbols.chars() {
+            if !SYMBOL.contains(&c) {
+                return Err(Error::InvalidSpec(format!("'{}' is not a symbol", c)));
+            }
+            mask |= ascii_bit(c)?;
+        }
+        self.symbols = mask;
+        Ok(())
+    }
+
+    /// The allowed symbols in the order of the symbol alphabet. Inverse of `set_symbols()`.
+    pub fn symbols(&self) -> String {
+        mask_to_string(self.symbols, &SYMBOL)
+    }
+
+    /// Never uses the characters in the string
+    /// ### Returns
+    /// `Error::InvalidSpec` if the string contains a non-ASCII character
+    pub fn set_excluded(&mut self, excluded: &str) -> Result<()> {
+        let mut mask = 0;
+        for c in excluded.chars() {
+            mask |= ascii_bit(c)?;
+        }
+        self.excluded = mask;
+        Ok(())
+    }
+
+    /// The excluded characters in ASCII order. Inverse of `set_excluded()`.
+    pub fn excluded(&self) -> String {
+        let ascii: Vec<char> = (0..128u8).map(char::from).collect();
+        mask_to_string(self.excluded, &ascii)
+    }
+
+    /// Checks whether the character may be used
+    pub fn allows(&self, c: char) -> bool {
+        let bit = match ascii_bit(c) {
+            Ok(bit) => bit,
+            Err(_) => return false
+        };
+        if SYMBOL.contains(&c) && self.symbols & bit == 0 {
+            return false;
+        }
+        self.excluded & bit == 0 && !(self.no_lookalikes && LOOKALIKES.contains(&c))
+    }
+
+    /// Builds the vector of legal characters for the key, keeping the order of `get_pass_building_chars()`
+    pub fn building_chars(&self, key: &Key) -> Vec<char> {
+        get_pass_building_chars(key).into_iter().filter(|c| self.allows(*c)).collect()
+    }
+
+    /// The characters left of every character type, enabled or not
+    /// ### Returns
+    /// Whether the type is enabled in the key and its allowed characters, for
+    /// uppercase, lowercase, numbers and symbols respectively
+    pub fn classes(&self, key: &Key) -> [(bool, Vec<char>); 4] {
+        let allowed = |chars: &[char]| chars.iter().copied().filter(|c| self.allows(*c)).collect();
+        [
+            (key.upper, allowed(&UPPER_CASE)),
+            (key.lower, allowed(&LOWER_CASE)),
+            (key.num, allowed(&NUM)),
+            (key.sym, allowed(&SYMBOL))
+        ]
+    }
+}
@@ -0,0 +1,282 @@
+//! Where copied passwords go. The system clipboard is used when there is one; on a
+//! headless machine or over SSH without X forwarding the password is copied through
+//! the terminal with the OSC 52 escape sequence instead, which the terminal emulator
+//! on the other end puts on its clipboard. Passwords can also be printed or not
+//! copied at all, see `ClipboardBackend`.
+
+use std::io::{IsTerminal, Write};
+use std::time::Duration;
+
+use project_stella::config::ClipboardBackend;
+
+/// A place copied passwords can be put
+pub trait Backend: Send {
+    /// Name of the backend as in the configuration file
+    fn name(&self) -> &'static str;
+
+    /// Puts the text on the clipboard
+    /// ### Returns
+    /// A message for the user if it could not be copied
+    fn copy(&mut self, text: &str) -> Result<(), String>;
+
+    /// Whether copied text stays around and has to be cleared after the timeout
+    fn clears(&self) -> bool {
+        false
+    }
+
+    /// Whether the text is printed to the terminal instead of copied, which full-screen
+    /// interfaces have to show themselves
+    fn prints(&self) -> bool {
+        false
+    }
+
+    /// Clears the clipboard if it still holds the text with the fingerprint
+    /// ### Returns
+    /// true if the clipboard was cleared
+    fn clear(&mut self, _copied: &blake2b_simd::Hash) -> bool {
+        false
+    }
+
+    /// Copies the text for the `--clip` of a command that exits right after, and clears it after the timeout
+    /// ### Params
+    /// - `text`: the text to copy
+    /// - `timeout`: time until it is cleared, zero to keep it
+    /// ### Side-effect
+    /// Blocks until the text was cleared, tells the user on stderr
+    fn hold(&mut self, text: &str, timeout: Duration) -> Result<(), String> {
+        self.copy(text)?;
+        if !self.clears() {
+            return Ok(());
+        }
+        if timeout.is_zero() {
+            eprintln!("Copied to clipboard.");
+            return Ok(());
+        }
+        eprintln!("Copied to clipboard. Clearing it in {} seconds.", timeout.as_secs());
+        std::thread::sleep(timeout);
+        self.clear(&fingerprint(text));
+        Ok(())
+    }
+}
+
+/// Identifies copied text without keeping it in memory
+pub fn fingerprint(text: &str) -> blake2b_simd::Hash {
+    blake2b_simd::Params::new().hash_length(32).hash(text.as_bytes())
+}
+
+/// Opens the configured backend
+/// ### Params
+/// - `choice`: the backend of the configuration, `Auto` falls back from the system
+///   clipboard to OSC 52 if there is a terminal, and to no clipboard otherwise
+/// ### Returns
+/// The backend or why the configured backend is unavailable
+/// ### Side-effect
+/// Tells on stderr which backend `Auto` fell back to
+pub fn open(choice: ClipboardBackend) -> Result<Box<dyn Backend>, String> {
+    match choice {
+        ClipboardBackend::Auto => match Native::open() {
+            Ok(native) => Ok(Box::new(native)),
+            Err(reason) => match Osc52::open() {
+                Ok(osc52) => {
+                    eprintln!("{}, copying through the terminal (OSC 52) instead.", reason);
+                    Ok(Box::new(osc52))
+                },
+                Err(_) => {
+                    eprintln!("{} and no terminal to copy through, passwords are not copied.", reason);
+                    Ok(Box::new(NoClipboard))
+                }
+            }
+        },
+        ClipboardBackend::Native => Ok(Box::new(Native::open()?)),
+        ClipboardBackend::Osc52 => Ok(Box::new(Osc52::open()?)),
+        ClipboardBackend::Stdout => Ok(Box::new(Stdout)),
+        ClipboardBackend::None => Ok(Box::new(NoClipboard))
+    }
+}
+
+/// The system clipboard
+struct Native {
+    clipboard: arboard::Clipboard
+}
+
+impl Native {
+    fn open() -> Result<Self, String> {
+        arboard::Clipboard::new()
+            .map(|clipboard| Native { clipboard })
+            .map_err(|err| format!("No system clipboard ({})", err))
+    }
+}
+
+/// Asks clipboard managers not to keep the copied text in their history, where the platform supports it
+/// ### Returns
+/// The builder with the exclusion hints set
+fn exclude_from_history(set: arboard::Set) -> arboard::Set {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        #[cfg(target_os = "linux")]
+        use arboard::SetExtLinux;
+        #[cfg(target_os = "macos")]
+        use arboard::SetExtApple;
+        set.exclude_from_history()
+    }
+
+    #[cfg(windows)]
+    {
+        use arboard::SetExtWindows;
+        set.exclude_from_monitoring()
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    set
+}
+
+impl Backend for Native {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn copy(&mut self, text: &str) -> Result<(), String> {
+        exclude_from_history(self.clipboard.set()).text(text.to_string())
+            .map_err(|err| format!("failed to copy to clipboard: {}", err))
+    }
+
+    fn clears(&self) -> bool {
+        true
+    }
+
+    fn clear(&mut self, copied: &blake2b_simd::Hash) -> bool {
+        let unchanged = self.clipboard.get_text().is_ok_and(|mut text| {
+            let unchanged = fingerprint(&text) == *copied;
+            zeroize::Zeroize::zeroize(&mut text);
+            unchanged
+        });
+        unchanged && self.clipboard.clear().is_ok()
+    }
+
+    /// On Linux the clipboard is owned by the process that set it, so this waits until
+    /// the timeout passed or something else replaces it; a timeout of 0 waits until it is replaced.
+    #[cfg(target_os = "linux")]
+    fn hold(&mut self, text: &str, timeout: Duration) -> Result<(), String> {
+        use arboard::SetExtLinux;
+        let set = exclude_from_history(self.clipboard.set());
+        let failed = |err: arboard::Error| format!("failed to copy to clipboard: {}", err);
+        if timeout.is_zero() {
+            eprintln!("Copied to clipboard. Keeping it available until it is replaced.");
+            return set.wait().text(text.to_string()).map_err(failed);
+        }
+        eprintln!("Copied to clipboard. Keeping it available for {} seconds or until it is replaced.", timeout.as_secs());
+        set.wait_until(std::time::Instant::now() + timeout).text(text.to_string()).map_err(failed)?;
+        self.clear(&fingerprint(text));
+        Ok(())
+    }
+}
+
+/// The clipboard of the terminal emulator, reached with the OSC 52 escape sequence.
+/// Terminals do not tell what their clipboard holds, so it is cleared after the
+/// timeout even if something else was copied since.
+struct Osc52 {
+    /// Inside tmux the sequence is passed through to the terminal around it
+    tmux: bool
+}
+
+impl Osc52 {
+    fn open() -> Result<Self, String> {
+        terminal().map_err(|err| format!("no terminal for OSC 52 ({})", err))?;
+        Ok(Osc52 { tmux: std::env::var_os("TMUX").is_some_and(|tmux| !tmux.is_empty()) })
+    }
+
+    /// Sets the clipboard of the terminal to the base64 payload, anything else clears it
+    fn send(&self, payload: &str) -> Result<(), String> {
+        let sequence = if self.tmux {
+            format!("\x1bPtmux;\x1b\x1b]52;c;{}\x07\x1b\\", payload)
+        } else {
+            format!("\x1b]52;c;{}\x07", payload)
+        };
+        let mut terminal = terminal().map_err(|err| format!("failed to write to the terminal: {}", err))?;
+        terminal.write_all(sequence.as_bytes())
+            .and_then(|_| terminal.flush())
+            .map_err(|err| format!("failed to write to the terminal: {}", err))
+    }
+}
+
+/// The terminal the password manager runs in, even when stdout is redirected
+fn terminal() -> std::io::Result<Box<dyn Write>> {
+    #[cfg(unix)]
+    if let Ok(tty) = std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+        return Ok(Box::new(tty));
+    }
+    if std::io::stdout().is_terminal() {
+        Ok(Box::new(std::io::stdout()))
+    } else {
+        Err(std::io::Error::new(std::io::ErrorKind::NotFound, "standard output is not a terminal"))
+    }
+}
+
+impl Backend for Osc52 {
+    fn name(&self) -> &'static str {
+        "osc52"
+    }
+
+    fn copy(&mut self, text: &str) -> Result<(), String> {
+        let mut payload = base64(text.as_bytes());
+        let result = self.send(&payload);
+        zeroize::Zeroize::zeroize(&mut payload);
+        result
+    }
+
+    fn clears(&self) -> bool {
+        true
+    }
+
+    fn clear(&mut self, _copied: &blake2b_simd::Hash) -> bool {
+        self.send("!").is_ok()
+    }
+}
+
+/// Encodes bytes as standard base64 with padding
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = chunk.iter().enumerate().fold(0u32, |triple, (index, byte)| triple | (*byte as u32) << (16 - 8 * index));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(triple >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Prints passwords to stdout instead of copying them
+struct Stdout;
+
+impl Backend for Stdout {
+    fn name(&self) -> &'static str {
+        "stdout"
+    }
+
+    fn copy(&mut self, text: &str) -> Result<(), String> {
+        println!("{}", text);
+        Ok(())
+    }
+
+    fn prints(&self) -> bool {
+        true
+    }
+}
+
+/// Does not copy passwords anywhere
+struct NoClipboard;
+
+impl Backend for NoClipboard {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn copy(&mut self, _text: &str) -> Result<(), String> {
+        Err("there is no clipboard to copy to, set clipboard.backend to \"stdout\" to print passwords".to_string())
+    }
+}
@@ -0,0 +1,107 @@
+//! Clears copied passwords from the clipboard after the configured timeout, but only
+//! if the clipboard still holds the copied password.
+
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use crate::clipboard::{self, Backend};
+
+/// How often the timer is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A password on the clipboard waiting to be cleared
+struct Copied {
+    fingerprint: blake2b_simd::Hash,
+    deadline: Instant
+}
+
+struct State {
+    backend: Box<dyn Backend>,
+    copied: Option<Copied>
+}
+
+/// Counts down until the copied password is cleared from the clipboard. A background
+/// thread clears it once the countdown ends.
+pub struct ClipboardTimer {
+    state: Arc<Mutex<State>>
+}
+
+impl ClipboardTimer {
+    /// Makes a timer with nothing to clear and starts its thread
+    /// ### Params
+    /// - `backend`: where passwords are copied to, kept as long as the timer
+    /// ### Side-effect
+    /// Spawns a thread that lives as long as the timer
+    pub fn new(backend: Box<dyn Backend>) -> Self {
+        let state = Arc::new(Mutex::new(State { backend, copied: None }));
+        let weak = Arc::downgrade(&state);
+        std::thread::spawn(move || clear_timer(weak));
+        ClipboardTimer { state }
+    }
+
+    /// Copies the text and starts counting down until it is cleared, replacing an earlier countdown
+    /// ### Params
+    /// - `text`: the text to copy, only its fingerprint is kept
+    /// - `timeout`: time until it is cleared, zero to keep it
+    /// ### Returns
+    /// A message for the user if it could not be copied
+    pub fn copy(&self, text: &str, timeout: Duration) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        state.backend.copy(text)?;
+        state.copied = (state.backend.clears() && !timeout.is_zero())
+            .then(|| Copied { fingerprint: clipboard::fingerprint(text), deadline: Instant::now() + timeout });
+        Ok(())
+    }
+
+    /// Name of the clipboard backend in use
+    pub fn backend_name(&self) -> &'static str {
+        self.state.lock().unwrap().backend.name()
+    }
+
+    /// Whether copied passwords stay on the clipboard until they are cleared,
+    /// false if they are printed or not copied at all
+    pub fn clears(&self) -> bool {
+        self.state.lock().unwrap().backend.clears()
+    }
+
+    /// Whether copied passwords are printed to the terminal, see `Backend::prints()`
+    pub fn prints(&self) -> bool {
+        self.state.lock().unwrap().backend.prints()
+    }
+
+    /// Seconds left, rounded up, until the copied password is cleared
+    /// ### Returns
+    /// None if no password waits to be cleared
+    pub fn seconds_left(&self) -> Option<u64> {
+        self.state.lock().unwrap().copied.as_ref().map(|copied| {
+            let left = copied.deadline.saturating_duration_since(Instant::now());
+            left.as_secs() + u64::from(left.subsec_nanos() > 0)
+        })
+    }
+
+    /// Clears the clipboard right away if it still holds the copied password, e.g. before exiting
+    pub fn clear_now(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(copied) = state.copied.take() {
+            state.backend.clear(&copied.fingerprint);
+        }
+    }
+}
+
+/// Clears the clipboard once the deadline passed. Stops when the timer is dropped.
+/// ### Side-effect
+/// Writes to the system clipboard
+fn clear_timer(state: Weak<Mutex<State>>) {
+    while let Some(state) = state.upgrade() {
+        {
+            let mut state = state.lock().unwrap();
+            let expired = state.copied.as_ref().is_some_and(|copied| copied.deadline <= Instant::now());
+            if expired {
+                let copied = state.copied.take().unwrap();
+                state.backend.clear(&copied.fingerprint);
+            }
+        }
+        drop(state);
+        std::thread::sleep(CHECK_INTERVAL);
+    }
+}
@@ -0,0 +1,706 @@
+//! Non-interactive subcommands so the password manager can be used from
+//! shell scripts and Makefiles, e.g.
+//! `project_stella gen --user alice --service github --num 2 --len 20 --classes ulns`
+
+use std::collections::HashMap;
+use std::io::{BufRead, Read};
+use std::time::Duration;
+use rusqlite::Connection;
+use zeroize::Zeroize;
+use project_stella::{auth, display, password_change, Algorithm, Charset, Generator, Key, Mode, PassphraseSpec, PasswordSpec, Rules, Variant};
+use project_stella::presets::Presets;
+use project_stella::search;
+use project_stella::service_db_actions::{self, ServiceRecord};
+use crate::clipboard;
+
+/// Flags that do not take a value
+const SWITCHES: [&str; 4] = ["--clip", "--grouped", "--qr", "--password-stdin"];
+
+/// Where the master password is read from
+enum PasswordSource {
+    /// Prompt on the controlling terminal without echo
+    Tty,
+    /// First line of standard input
+    Stdin,
+    /// First line of an already opened file descriptor
+    Fd(i32)
+}
+
+/// Flags given to a subcommand
+struct Flags {
+    values: HashMap<String, String>,
+    switches: Vec<String>
+}
+
+impl Flags {
+    /// Parses `--name value` pairs and `--switch`es
+    /// ### Returns
+    /// The parsed flags or an error message for unknown or incomplete flags
+    fn parse(args: &[String], allowed: &[&str]) -> Result<Self, String> {
+        let mut flags = Flags { values: HashMap::new(), switches: Vec::new() };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if !allowed.contains(&arg.as_str()) {
+                return Err(format!("unexpected argument '{}'", arg));
+            }
+            if SWITCHES.contains(&arg.as_str()) {
+                flags.switches.push(arg.clone());
+                continue;
+            }
+            match iter.next() {
+                Some(value) => flags.values.insert(arg.clone(), value.clone()),
+                None => return Err(format!("missing value for '{}'", arg))
+            };
+        }
+        Ok(flags)
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|value| value.as_str())
+    }
+
+    fn require(&self, name: &str) -> Result<&str, String> {
+        self.get(name).ok_or(format!("'{}' is required", name))
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.switches.iter().any(|switch| switch == name)
+    }
+
+    /// Parses the value of a yes/no flag, `default` if the flag is missing
+    fn yes_no(&self, name: &str, default: bool) -> Result<bool, String> {
+        match self.get(name) {
+            Some("yes") => Ok(true),
+            Some("no") => Ok(false),
+            Some(_) => Err(format!("'{}' must be 'yes' or 'no'", name)),
+            None => Ok(default)
+        }
+    }
+
+    /// Parses the value of a numeric flag, `default` if the flag is missing
+    fn number(&self, name: &str, default: u8) -> Result<u8, String> {
+        match self.get(name) {
+            Some(value) => value.parse().map_err(|_| format!("'{}' must be a number between 0 and 255", name)),
+            None => Ok(default)
+        }
+    }
+
+    /// Parses the value of a flag taking a larger number, `default` if the flag is missing
+    fn large_number(&self, name: &str, default: u32) -> Result<u32, String> {
+        match self.get(name) {
+            Some(value) => value.parse().map_err(|_| format!("'{}' must be a number between 0 and {}", name, u32::MAX)),
+            None => Ok(default)
+        }
+    }
+
+    /// Picks the password source from `--password-stdin` and `--password-fd`
+    fn password_source(&self) -> Result<PasswordSource, String> {
+        match (self.has("--password-stdin"), self.get("--password-fd")) {
+            (true, Some(_)) => Err("'--password-stdin' and '--password-fd' cannot be used together".to_string()),
+            (true, None) => Ok(PasswordSource::Stdin),
+            // stdin, stdout and stderr are not taken, stdin is read with '--password-stdin'
+            (false, Some(fd)) => match fd.parse() {
+                Ok(fd) if fd > 2 => Ok(PasswordSource::Fd(fd)),
+                Ok(_) => Err("'--password-fd' cannot be 0, 1 or 2, use '--password-stdin' to read stdin".to_string()),
+                Err(_) => Err("'--password-fd' must be a file descriptor number".to_string())
+            },
+            (false, None) => Ok(PasswordSource::Tty)
+        }
+    }
+}
+
+/// Reads the first line from the reader without its line ending
+fn read_first_line(reader: &mut dyn BufRead) -> Result<String, String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|err| format!("failed to read password: {}", err))?;
+    let trimmed = line.trim_end_matches(['\n', '\r']).to_string();
+    line.zeroize();
+    Ok(trimmed)
+}
+
+/// Reads the master password from the given source
+/// ### Side-effect
+/// Reads from the terminal, stdin or the file descriptor
+fn read_password(source: &PasswordSource, prompt: &str) -> Result<String, String> {
+    match source {
+        PasswordSource::Tty => rpassword::prompt_password(prompt)
+            .map_err(|err| format!("failed to read password from terminal: {}", err)),
+        PasswordSource::Stdin => read_first_line(&mut std::io::stdin().lock()),
+        PasswordSource::Fd(fd) => read_password_fd(*fd)
+    }
+}
+
+#[cfg(unix)]
+fn read_password_fd(fd: i32) -> Result<String, String> {
+    use std::mem::ManuallyDrop;
+    use std::os::unix::io::FromRawFd;
+    // SAFETY: the caller hands us the descriptor to read the password from. The file
+    // is never dropped, so the descriptor stays open and belongs to the caller.
+    let mut file = ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+    let mut reader = std::io::BufReader::new((&mut *file).take(4096));
+    read_first_line(&mut reader)
+}
+
+#[cfg(not(unix))]
+fn read_password_fd(_fd: i32) -> Result<String, String> {
+    Err("'--password-fd' is only supported on unix".to_string())
+}
+
+/// `gen`: generates a password and prints it, as text or QR code, or copies it to the clipboard
+fn gen(args: &[String]) -> Result<(), String> {
+    let flags = Flags::parse(args, &["--user", "--service", "--num", "--len", "--classes", "--algorithm",
+                                     "--symbols", "--exclude", "--no-lookalikes", "--rules",
+                                     "--mode", "--separator", "--capitalize", "--digit",
+                                     "--password-stdin", "--password-fd", "--clip", "--grouped", "--qr"])?;
+    if ["--clip", "--grouped", "--qr"].iter().filter(|output| flags.has(output)).count() > 1 {
+        return Err("only one of '--clip', '--grouped' and '--qr' can be given".to_string());
+    }
+    let username = flags.require("--user")?;
+    let service_title = flags.require("--service")?.trim().to_string();
+    if service_title.is_empty() {
+        return Err("'--service' cannot be empty".to_string());
+    }
+    let source = flags.password_source()?;
+
+    let auth_conn = auth::get_connection(crate::paths().auth_db()).map_err(|err| err.to_string())?;
+    if !auth::user_exists(&auth_conn, username) {
+        return Err(format!("unknown user '{}', create it with 'user add' first", username));
+    }
+
+    let service_conn = service_db_actions::get_connection(crate::paths().services_db()).map_err(|err| err.to_string())?;
+    // flags override the recipe remembered for the service
+    let mut record = match service_db_actions::read_service(&service_conn, username, &service_title) {
+        Some(record) => record,
+        // a new record would give another password than the one saved before services belonged to users
+        None if service_db_actions::is_unowned(&service_conn, &service_title).map_err(|err| err.to_string())? => {
+            return Err(format!("'{}' was saved before every user had their own services, \
+                                claim it with 'services assign --user {}' first", service_title, username));
+        },
+        None => new_service(&service_conn, username, &service_title)?
+    };
+    record.pass_num = flags.number("--num", record.pass_num)?;
+    match flags.get("--mode") {
+        Some("password") => record.spec.set_mode(Mode::Password),
+        Some("passphrase") if !matches!(record.spec.mode, Mode::Passphrase(_)) => {
+            record.spec.set_mode(Mode::Passphrase(PassphraseSpec::default()));
+        },
+        Some("passphrase") => (),
+        Some("pin") => record.spec.set_mode(Mode::Pin),
+        Some(_) => return Err("'--mode' must be 'password', 'passphrase' or 'pin'".to_string()),
+        None => ()
+    }
+    record.spec.mode = passphrase_options(&flags, record.spec.mode)?;
+    record.spec.length = flags.number("--len", record.spec.length)?;
+    if let Some(classes) = flags.get("--classes") {
+        record.spec.key = Key::from_classes(classes).map_err(|err| err.to_string())?;
+    }
+    password_options(&flags, &mut record.spec)?;
+    if flags.get("--algorithm").is_some() {
+        let number = flags.number("--algorithm", 0)?;
+        record.spec.algorithm = Algorithm::from_number(number).map_err(|err| err.to_string())?;
+    }
+    record.spec.validate().map_err(|err| err.to_string())?;
+
+    let mut password = read_password(&source, "Password: ")?;
+    if !auth::verify_user(&auth_conn, username, &password) {
+        password.zeroize();
+        return Err("password did not match login password".to_string());
+    }
+
+    let profile = auth::read_profile(&auth_conn, username).ok_or("the stored KDF profile is invalid".to_string())?;
+    let mut generated_pass = profile
+        .generate(&mut password, &[username, record.salt_part().as_str()], &record.spec)
+        .map_err(|err| err.to_string())?;
+    // a password whose recipe is not remembered could not be generated again
+    if let Err(err) = service_db_actions::update_db(&service_conn, username, &record) {
+        generated_pass.zeroize();
+        return Err(format!("cannot remember '{}': {}", record.title, err));
+    }
+    let _ = service_db_actions::record_use(&service_conn, username, &record.title, search::now());
+
+    let result = if flags.has("--clip") {
+        copy_to_clipboard(&generated_pass)
+    } else if flags.has("--qr") {
+        display::qr_code(&generated_pass).map(|code| println!("{}", code)).map_err(|err| err.to_string())
+    } else if flags.has("--grouped") {
+        println!("{}", display::grouped(&generated_pass, display::GROUP_SIZE));
+        Ok(())
+    } else {
+        println!("{}", generated_pass);
+        Ok(())
+    };
+    generated_pass.zeroize();
+    result
+}
+
+/// Makes the record of a service that has not been used before, filled in with its site preset
+/// ### Side-effect
+/// Reads the user presets file, tells on stderr which preset was applied
+fn new_service(conn: &Connection, username: &str, title: &str) -> Result<ServiceRecord, String> {
+    let mut record = ServiceRecord::new(title, 1);
+    // another service may still derive its password from the title, e.g. after a rename
+    record.derivation_id = service_db_actions::unused_derivation_id(conn, username, title).map_err(|err| err.to_string())?;
+    record.spec = crate::config().spec();
+    let presets = Presets::load(crate::paths().presets()).unwrap_or_else(|err| {
+        eprintln!("Ignoring user presets, {}", err);
+        Presets::bundled()
+    });
+    if let Some(preset) = presets.find(title) {
+        let mut spec = record.spec;
+        match preset.apply(&mut spec) {
+            Ok(()) => {
+                record.spec = spec;
+                eprintln!("Applied the site preset for '{}'.", title);
+            },
+            Err(err) => eprintln!("Ignoring the site preset for '{}', {}", title, err)
+        }
+    }
+    Ok(record)
+}
+
+/// Applies `--separator`, `--capitalize` and `--digit` to a passphrase mode
+/// ### Returns
+/// The updated mode or an error if the options are given for passwords
+fn passphrase_options(flags: &Flags, mode: Mode) -> Result<Mode, String> {
+    let options = ["--separator", "--capitalize", "--digit"];
+    let mut passphrase = match mode {
+        Mode::Passphrase(passphrase) => passphrase,
+        Mode::Password | Mode::Pin => match options.iter().find(|option| flags.get(option).is_some()) {
+            Some(option) => return Err(format!("'{}' only applies to '--mode passphrase'", option)),
+            None => return Ok(mode)
+        }
+    };
+
+    if let Some(separator) = flags.get("--separator") {
+        let mut chars = separator.chars();
+        passphrase.separator = match (separator, chars.next(), chars.next()) {
+            ("none", _, _) => None,
+            (_, Some(c), None) => Some(c),
+            _ => return Err("'--separator' must be a single character or 'none'".to_string())
+        };
+    }
+    passphrase.capitalize = flags.yes_no("--capitalize", passphrase.capitalize)?;
+    passphrase.digit = flags.yes_no("--digit", passphrase.digit)?;
+    Ok(Mode::Passphrase(passphrase))
+}
+
+/// Applies `--symbols`, `--exclude`, `--no-lookalikes` and `--rules` to the spec of a password
+/// ### Returns
+/// An error if the options are invalid or given for passphrases and PINs
+fn password_options(flags: &Flags, spec: &mut PasswordSpec) -> Result<(), String> {
+    let options = ["--symbols", "--exclude", "--no-lookalikes", "--rules"];
+    if spec.mode != Mode::Password {
+        return match options.iter().find(|option| flags.get(option).is_some()) {
+            Some(option) => Err(format!("'{}' only applies to '--mode password'", option)),
+            None => Ok(())
+        };
+    }
+
+    match flags.get("--symbols") {
+        Some("all") => spec.charset.set_symbols(&Charset::default().symbols()),
+        Some(symbols) => spec.charset.set_symbols(symbols),
+        None => Ok(())
+    }.map_err(|err| err.to_string())?;
+    if let Some(excluded) = flags.get("--exclude") {
+        spec.charset.set_excluded(excluded).map_err(|err| err.to_string())?;
+    }
+    spec.charset.no_lookalikes = flags.yes_no("--no-lookalikes", spec.charset.no_lookalikes)?;
+    match flags.get("--rules") {
+        Some("none") => spec.rules = Rules::default(),
+        Some(rules) => spec.rules = Rules::parse(rules).map_err(|err| err.to_string())?,
+        None => ()
+    }
+    Ok(())
+}
+
+/// Copies the text with the configured clipboard backend and clears it after the clipboard timeout
+fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    let mut clipboard = clipboard::open(crate::config().clipboard_backend.value)?;
+    clipboard.hold(text, Duration::from_secs(crate::config().clipboard_timeout.value))
+}
+
+/// Reads `--user` and makes sure the user exists
+fn existing_user(flags: &Flags) -> Result<&str, String> {
+    let username = flags.require("--user")?;
+    let conn = auth::get_connection(crate::paths().auth_db()).map_err(|err| err.to_string())?;
+    if !auth::user_exists(&conn, username) {
+        return Err(format!("unknown user '{}'", username));
+    }
+    Ok(username)
+}
+
+/// `services list`: prints every service of a user and its recipe, tab separated.
+/// The classes column shows `words` for passphrases and `digits` for PINs.
+/// `services assign`: gives the services saved before services belonged to users to a user.
+/// `services rename`, `delete`, `alias`, `unalias` and `merge` tidy up the services of a user,
+/// `services aliases` prints every alias and its service, tab separated.
+/// `services describe` prints or changes the notes and tags of a service.
+fn services(args: &[String]) -> Result<(), String> {
+    match args.first().map(|arg| arg.as_str()) {
+        Some("list") => {
+            let flags = Flags::parse(&args[1..], &["--user"])?;
+            let username = existing_user(&flags)?;
+            let conn = service_db_actions::get_connection(crate::paths().services_db()).map_err(|err| err.to_string())?;
+            let services = service_db_actions::read_all_services(&conn, username).map_err(|err| err.to_string())?;
+            for record in services {
+                let spec = record.spec;
+                let classes = match spec.mode {
+                    Mode::Password => spec.key.to_classes(),
+                    Mode::Passphrase(_) => "words".to_string(),
+                    Mode::Pin => "digits".to_string()
+                };
+                println!("{}\t{}\t{}\t{}\t{}", record.title, record.pass_num, spec.length, classes, spec.algorithm.number());
+            }
+            Ok(())
+        },
+        Some("assign") => {
+            let flags = Flags::parse(&args[1..], &["--user"])?;
+            let username = existing_user(&flags)?;
+            let conn = service_db_actions::get_connection(crate::paths().services_db()).map_err(|err| err.to_string())?;
+            let assigned = service_db_actions::assign_unowned(&conn, username).map_err(|err| err.to_string())?;
+            let left = service_db_actions::count_unowned(&conn).map_err(|err| err.to_string())?;
+            eprintln!("Assigned {} services to '{}'.", assigned, username);
+            if left > 0 {
+                eprintln!("{} services were left unassigned since '{}' already has services of the same title.", left, username);
+            }
+            Ok(())
+        },
+        Some("aliases") => {
+            let flags = Flags::parse(&args[1..], &["--user"])?;
+            let username = existing_user(&flags)?;
+            let conn = service_db_actions::get_connection(crate::paths().services_db()).map_err(|err| err.to_string())?;
+            for alias in service_db_actions::read_aliases(&conn, username).map_err(|err| err.to_string())? {
+                println!("{}\t{}", alias.alias, alias.title);
+            }
+            Ok(())
+        },
+        Some("describe") => {
+            let flags = Flags::parse(&args[1..], &["--user", "--service", "--notes", "--tags"])?;
+            let username = existing_user(&flags)?;
+            let service = flags.require("--service")?.trim();
+            let conn = service_db_actions::get_connection(crate::paths().services_db()).map_err(|err| err.to_string())?;
+            let record = service_db_actions::read_service(&conn, username, service).ok_or(format!("unknown service '{}'", service))?;
+            if flags.get("--notes").is_none() && flags.get("--tags").is_none() {
+                println!("notes\t{}", record.notes);
+                println!("tags\t{}", record.tags.join(", "));
+                return Ok(());
+            }
+            let notes = flags.get("--notes").map_or(record.notes, str::to_string);
+            let tags = flags.get("--tags").map_or(record.tags, service_db_actions::parse_tags);
+            service_db_actions::update_details(&conn, username, &record.title, &notes, &tags).map_err(|err| err.to_string())?;
+            eprintln!("Changed the notes and tags of '{}'.", record.title);
+            Ok(())
+        },
+        Some(command @ ("rename" | "delete" | "alias" | "unalias" | "merge")) => {
+            let allowed: &[&str] = match command {
+                "rename" => &["--user", "--service", "--to"],
+                "alias" => &["--user", "--service", "--alias"],
+                "unalias" => &["--user", "--alias"],
+                "merge" => &["--user", "--service", "--into"],
+                _ => &["--user", "--service"]
+            };
+            let flags = Flags::parse(&args[1..], allowed)?;
+            let username = existing_user(&flags)?;
+            let title = |flag: &str| flags.require(flag).map(|title| title.trim().to_string());
+            let conn = service_db_actions::get_connection(crate::paths().services_db()).map_err(|err| err.to_string())?;
+            let result = match command {
+                "rename" => {
+                    let (service, new_title) = (title("--service")?, title("--to")?);
+                    service_db_actions::rename_service(&conn, username, &service, &new_title)
+                        .map(|()| (Some((service.clone(), new_title.clone())), format!("Renamed '{}' to '{}', its password is unchanged.", service, new_title)))
+                },
+                "delete" => {
+                    let service = title("--service")?;
+                    service_db_actions::delete_service(&conn, username, &service)
+                        .map(|()| (None, format!("Deleted '{}' and its aliases.", service)))
+                },
+                "alias" => {
+                    let (service, alias) = (title("--service")?, title("--alias")?);
+                    service_db_actions::add_alias(&conn, username, &alias, &service)
+                        .map(|()| (None, format!("'{}' gives the password of '{}' now.", alias, service)))
+                },
+                "unalias" => {
+                    let alias = title("--alias")?;
+                    service_db_actions::remove_alias(&conn, username, &alias)
+                        .map(|()| (None, format!("Removed the alias '{}'.", alias)))
+                },
+                _ => {
+                    let (service, into) = (title("--service")?, title("--into")?);
+                    // merging into an alias merges into its service
+                    let into = service_db_actions::read_service(&conn, username, &into).map_or(into, |record| record.title);
+                    service_db_actions::merge_services(&conn, username, &service, &into)
+                        .map(|()| (Some((service.clone(), into.clone())), format!("Merged '{}' into '{}', both give the password of '{}' now.", service, into, into)))
+                }
+            };
+            let (moved, message) = result.map_err(|err| err.to_string())?;
+            // a pending change of the master password follows the service to its new title
+            if let Some((service, new_title)) = moved {
+                let auth_conn = auth::get_connection(crate::paths().auth_db()).map_err(|err| err.to_string())?;
+                password_change::rename_service(&auth_conn, username, &service, &new_title).map_err(|err| err.to_string())?;
+            }
+            eprintln!("{}", message);
+            Ok(())
+        },
+        _ => Err("usage: services <list|aliases|assign> --user <name> | services rename --user <name> --service <title> --to <title> \
+                  | services delete --user <name> --service <title> | services alias --user <name> --service <title> --alias <name> \
+                  | services unalias --user <name> --alias <name> | services merge --user <name> --service <title> --into <title> \
+                  | services describe --user <name> --service <title> [--notes <text>] [--tags <tags>]".to_string())
+    }
+}
+
+/// Reads a KDF profile from `--memory`, `--iterations`, `--parallelism` and `--variant`,
+/// the parameters of the configured profile for the flags that are left out
+fn profile_options(flags: &Flags) -> Result<Generator, String> {
+    let default = crate::config().profile().map_err(|err| err.to_string())?;
+    let variant = match flags.get("--variant") {
+        Some(variant) => Variant::from_str(variant)
+            .map_err(|_| "'--variant' must be 'argon2d', 'argon2i' or 'argon2id'".to_string())?,
+        None => default.variant()
+    };
+    Generator::with_profile(
+        flags.large_number("--memory", default.mem_cost())?,
+        flags.large_number("--iterations", default.time_cost())?,
+        flags.large_number("--parallelism", default.lanes())?,
+        variant
+    ).map_err(|err| err.to_string())
+}
+
+/// Describes a KDF profile as the `user add` flags making it
+fn profile_flags(profile: &Generator) -> String {
+    format!("--memory {} --iterations {} --parallelism {} --variant {}",
+            profile.mem_cost(), profile.time_cost(), profile.lanes(), profile.variant().as_lowercase_str())
+}
+
+/// `user add`: creates a new user. The password is confirmed when read from the terminal.
+/// `user profile`: prints the KDF profile of a user.
+fn user(args: &[String]) -> Result<(), String> {
+    match args.first().map(|arg| arg.as_str()) {
+        Some("add") => {
+            let flags = Flags::parse(&args[1..], &["--user", "--password-stdin", "--password-fd",
+                                                   "--memory", "--iterations", "--parallelism", "--variant"])?;
+            let username = flags.require("--user")?.trim();
+            if username.is_empty() {
+                return Err("'--user' cannot be empty".to_string());
+            }
+            let source = flags.password_source()?;
+            let profile = profile_options(&flags)?;
+
+            let conn = auth::get_connection(crate::paths().auth_db()).map_err(|err| err.to_string())?;
+            if auth::user_exists(&conn, username) {
+                return Err(format!("user '{}' already exists", username));
+            }
+
+            let mut password = read_password(&source, "Password: ")?;
+            if let PasswordSource::Tty = source {
+                let mut confirm_pass = read_password(&source, "Confirm password: ")?;
+                let matches = confirm_pass == password;
+                confirm_pass.zeroize();
+                if !matches {
+                    password.zeroize();
+                    return Err("passwords did not match".to_string());
+                }
+            }
+
+            let added = auth::add_user_with_profile(&conn, username, &password, &profile);
+            password.zeroize();
+            if !added {
+                return Err(format!("user '{}' already exists", username));
+            }
+            eprintln!("Added user '{}'.", username);
+            Ok(())
+        },
+        Some("profile") => {
+            let flags = Flags::parse(&args[1..], &["--user"])?;
+            let username = flags.require("--user")?;
+            let conn = auth::get_connection(crate::paths().auth_db()).map_err(|err| err.to_string())?;
+            if !auth::user_exists(&conn, username) {
+                return Err(format!("unknown user '{}'", username));
+            }
+            let profile = auth::read_profile(&conn, username).ok_or("the stored KDF profile is invalid".to_string())?;
+            println!("{}", profile_flags(&profile));
+            Ok(())
+        },
+        _ => Err("usage: user add --user <name> [profile flags] | user profile --user <name>".to_string())
+    }
+}
+
+/// `bench`: suggests a KDF profile that takes about the target time on this machine.
+/// Memory is doubled first since it is what makes attacks expensive, then iterations are added.
+fn bench(args: &[String]) -> Result<(), String> {
+    let flags = Flags::parse(args, &["--target-ms", "--max-memory", "--parallelism", "--variant"])?;
+    let target = std::time::Duration::from_millis(flags.large_number("--target-ms", 1000)? as u64);
+    let max_memory = flags.large_number("--max-memory", 1024 * 1024)?;
+    let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get() as u32);
+    let lanes = flags.large_number("--parallelism", cores)?;
+    let variant = match flags.get("--variant") {
+        Some(variant) => Variant::from_str(variant)
+            .map_err(|_| "'--variant' must be 'argon2d', 'argon2i' or 'argon2id'".to_string())?,
+        None => Variant::Argon2id
+    };
+    let measure = |mem_cost: u32, time_cost: u32| -> Result<std::time::Duration, String> {
+        let profile = Generator::with_profile(mem_cost, time_cost, lanes, variant).map_err(|err| err.to_string())?;
+        let elapsed = profile.measure().map_err(|err| err.to_string())?;
+        eprintln!("{:>8} KiB, {:>2} iterations: {:>6} ms", mem_cost, time_cost, elapsed.as_millis());
+        Ok(elapsed)
+    };
+
+    let default = Generator::new();
+    eprintln!("Default profile ({}):", profile_flags(&default));
+    eprintln!("{:>8} ms", default.measure().map_err(|err| err.to_string())?.as_millis());
+    eprintln!("Searching for {} ms with {} lanes:", target.as_millis(), lanes);
+
+    let time_cost = 3;
+    let mut mem_cost = (16 * 1024).max(8 * lanes).min(max_memory);
+    let mut elapsed = measure(mem_cost, time_cost)?;
+    while elapsed > target && mem_cost / 2 >= 8 * lanes {
+        mem_cost /= 2;
+        elapsed = measure(mem_cost, time_cost)?;
+    }
+    while mem_cost.saturating_mul(2) <= max_memory {
+        let doubled = measure(mem_cost * 2, time_cost)?;
+        if doubled > target {
+            break;
+        }
+        mem_cost *= 2;
+        elapsed = doubled;
+    }
+    let mut time_cost = time_cost;
+    loop {
+        let more = measure(mem_cost, time_cost + 1)?;
+        if more > target {
+            break;
+        }
+        time_cost += 1;
+        elapsed = more;
+    }
+
+    let suggestion = Generator::with_profile(mem_cost, time_cost, lanes, variant).map_err(|err| err.to_string())?;
+    eprintln!("Suggested profile, about {} ms per password:", elapsed.as_millis());
+    println!("{}", profile_flags(&suggestion));
+    Ok(())
+}
+
+/// Prints the settings in effect, one `name = value` per line followed by where it came from
+pub fn print_config() {
+    for line in config_lines() {
+        println!("{}", line);
+    }
+}
+
+/// The lines `print_config()` prints
+pub fn config_lines() -> Vec<String> {
+    let config = crate::config();
+    let data_dir = config.data_dir.value.as_ref().map(|dir| dir.display().to_string()).unwrap_or_default();
+    let settings = [
+        ("length", config.length.value.to_string(), &config.length.source),
+        ("classes", format!("\"{}\"", config.classes.value.to_classes()), &config.classes.source),
+        ("data_dir", format!("\"{}\"", data_dir), &config.data_dir.source),
+        ("kdf.memory", config.kdf_memory.value.to_string(), &config.kdf_memory.source),
+        ("kdf.iterations", config.kdf_iterations.value.to_string(), &config.kdf_iterations.source),
+        ("kdf.parallelism", config.kdf_parallelism.value.to_string(), &config.kdf_parallelism.source),
+        ("kdf.variant", format!("\"{}\"", config.kdf_variant.value.as_lowercase_str()), &config.kdf_variant.source),
+        ("clipboard.timeout", config.clipboard_timeout.value.to_string(), &config.clipboard_timeout.source),
+        ("clipboard.backend", format!("\"{}\"", config.clipboard_backend.value), &config.clipboard_backend.source),
+        ("ui.prompt", format!("{:?}", config.prompt.value), &config.prompt.source),
+        ("ui.session_idle_minutes", config.session_idle_minutes.value.to_string(), &config.session_idle_minutes.source)
+    ];
+    let mut lines = vec![format!("# configuration file: {}", crate::paths().config_file().display())];
+    lines.extend(settings.iter().map(|(name, value, source)| format!("{:<24} = {:<24} # {}", name, value, source)));
+    lines
+}
+
+/// `config`: prints the settings in effect
+fn config(args: &[String]) -> Result<(), String> {
+    Flags::parse(args, &[])?;
+    print_config();
+    Ok(())
+}
+
+/// Prints out the usage of the subcommands
+fn help() {
+    println!("Usage: project_stella [--data-dir <dir>] [COMMAND]");
+    println!("Without a command the interactive password manager is started.");
+    println!();
+    println!("Users and services are kept in $XDG_DATA_HOME/stellar (~/.local/share/stellar by default),");
+    println!("or in --data-dir or $STELLAR_HOME if given. Site presets are read from");
+    println!("$XDG_CONFIG_HOME/stellar/presets.toml (~/.config/stellar by default).");
+    println!();
+    println!("Commands:");
+    println!("  gen --user <name> --service <title> [--num <n>] [--len <n>] [--classes <ulns>] [--algorithm <n>] [--clip|--grouped|--qr]");
+    println!("      Generates a password and prints it to stdout (or copies it with --clip).");
+    println!("      --grouped prints it in groups of {} for reading it aloud, --qr prints it as a QR code.", display::GROUP_SIZE);
+    println!("      --clip waits {} seconds (clipboard.timeout) and clears the clipboard unless something else was copied.",
+             crate::config().clipboard_timeout.value);
+    println!("      Without a system clipboard it is copied through the terminal with OSC 52 (clipboard.backend).");
+    println!("      --classes takes any of u(ppercase), l(owercase), n(umbers), s(ymbols).");
+    println!("      --algorithm is the generation algorithm version.");
+    println!("      --symbols <chars|all> limits the symbols, --exclude <chars> drops characters (empty for none)");
+    println!("      and --no-lookalikes <yes|no> leaves out 0, O, 1, l and I.");
+    println!("      --rules <rules|none> sets a policy like 'n>=2,s>=2,s<=4,repeat<=2,letter-first' (algorithm 2+).");
+    println!("      --mode passphrase makes a passphrase of --len words from the EFF wordlist instead.");
+    println!("      Passphrases take --separator <char|none>, --capitalize <yes|no> and --digit <yes|no>.");
+    println!("      --mode pin makes a PIN of --len digits. Weak PINs like 1111 or 1234 are never generated.");
+    println!("      Flags that are left out are taken from the recipe the service was last generated with.");
+    println!("      New services default to --len {} --classes {} and the newest algorithm, or to their site preset.",
+             crate::config().length.value, crate::config().classes.value.to_classes());
+    println!("  services list --user <name>");
+    println!("      Prints every service of the user with its password number, length, classes and algorithm.");
+    println!("  services assign --user <name>");
+    println!("      Gives the services saved before services belonged to users to the user.");
+    println!("  services rename --user <name> --service <title> --to <title>");
+    println!("      Renames a service, e.g. to fix a typo. Its password stays the same.");
+    println!("  services delete --user <name> --service <title>");
+    println!("      Forgets a service and its aliases.");
+    println!("  services alias --user <name> --service <title> --alias <name>");
+    println!("      Gives a service another name sharing its recipe and password, e.g. google for gmail.");
+    println!("  services unalias --user <name> --alias <name>");
+    println!("      Forgets another name of a service.");
+    println!("  services aliases --user <name>");
+    println!("      Prints every alias of the user's services and the service it belongs to.");
+    println!("  services merge --user <name> --service <title> --into <title>");
+    println!("      Merges a duplicate into a service: the duplicate becomes an alias and gives the");
+    println!("      password of the service, its own password is no longer generated.");
+    println!("  services describe --user <name> --service <title> [--notes <text>] [--tags <tag,tag>]");
+    println!("      Prints the notes and tags of a service, or changes them. They never change its password.");
+    println!("  user add --user <name> [--memory <KiB>] [--iterations <n>] [--parallelism <n>] [--variant <argon2id>]");
+    println!("      Creates a new user. The Argon2 profile is used for all of the user's passwords and");
+    println!("      cannot be changed later without changing them. Defaults: {}",
+             crate::config().profile().map(|profile| profile_flags(&profile)).unwrap_or_default());
+    println!("  user profile --user <name>");
+    println!("      Prints the Argon2 profile of the user.");
+    println!("  bench [--target-ms <ms>] [--max-memory <KiB>] [--parallelism <n>] [--variant <argon2id>]");
+    println!("      Measures Argon2 on this machine and suggests a profile taking about --target-ms (default 1000).");
+    println!("  config");
+    println!("      Prints the settings in effect and where each came from.");
+    println!("  help");
+    println!("      This message.");
+    println!();
+    println!("The master password is read from the terminal unless one of these is given:");
+    println!("  --password-stdin    read it from the first line of stdin");
+    println!("  --password-fd <fd>  read it from the first line of the file descriptor (3 or higher)");
+}
+
+/// Runs the subcommand given on the command line
+/// ### Params
+/// - `args`: the command line arguments without the program name
+/// ### Side-effect
+/// Exits the process with status 1 if the command fails
+pub fn run(args: &[String]) {
+    let result = match args[0].as_str() {
+        "gen" => gen(&args[1..]),
+        "services" => services(&args[1..]),
+        "user" => user(&args[1..]),
+        "bench" => bench(&args[1..]),
+        "config" => config(&args[1..]),
+        "help" | "--help" | "-h" => {
+            help();
+            Ok(())
+        },
+        other => Err(format!("unknown command '{}'. Run 'help' for the list of commands.", other))
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,280 @@
+//! Defaults of the password manager read from `config.toml` in the configuration
+//! directory. Every value is optional, left out values keep the built-in default:
+//!
+//! ```toml
+//! length = 20                    # password length of new services
+//! classes = "ulns"               # character types of new services, like in `Key::from_classes()`
+//! data_dir = "/path/to/data"     # where users and services are kept, see `paths`
+//!
+//! [kdf]                          # Argon2 profile of new users
+//! memory = 65536                 # KiB
+//! iterations = 3
+//! parallelism = 4
+//! variant = "argon2id"
+//!
+//! [clipboard]
+//! timeout = 30                   # seconds until a copied password is cleared, 0 keeps it
+//! backend = "auto"               # where passwords are copied to, see `ClipboardBackend`
+//!
+//! [ui]
+//! prompt = "stellar> "          # in front of the search line
+//! session_idle_minutes = 10      # default idle time before a session locks
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::characters::Key;
+use crate::error::{Error, Result};
+use crate::generator::{Algorithm, Generator, PasswordSpec, DEFAULT_PASS_LEN};
+use argon2::Variant;
+
+/// Name of the configuration file in the configuration directory
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Where a setting in effect came from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// Built into the password manager
+    Default,
+    /// Read from the configuration file at the path
+    File(PathBuf),
+    /// Read from the environment variable
+    Environment(String),
+    /// Given on the command line with the flag
+    CommandLine(String)
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Environment(name) => write!(f, "environment variable {}", name),
+            Source::CommandLine(flag) => write!(f, "command line {}", flag)
+        }
+    }
+}
+
+/// Where copied passwords go
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClipboardBackend {
+    /// The system clipboard, or the terminal through OSC 52 if there is none
+    #[default]
+    Auto,
+    /// The system clipboard
+    Native,
+    /// The clipboard of the terminal through the OSC 52 escape sequence, works over SSH
+    Osc52,
+    /// Printed to standard output
+    Stdout,
+    /// Nowhere, passwords are not copied
+    None
+}
+
+impl ClipboardBackend {
+    /// Reads a backend by its name in the configuration file
+    /// ### Returns
+    /// The backend or None for an unknown name
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(ClipboardBackend::Auto),
+            "native" => Some(ClipboardBackend::Native),
+            "osc52" => Some(ClipboardBackend::Osc52),
+            "stdout" => Some(ClipboardBackend::Stdout),
+            "none" => Some(ClipboardBackend::None),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for ClipboardBackend {
+    /// Writes the name `parse()` reads
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ClipboardBackend::Auto => "auto",
+            ClipboardBackend::Native => "native",
+            ClipboardBackend::Osc52 => "osc52",
+            ClipboardBackend::Stdout => "stdout",
+            ClipboardBackend::None => "none"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A setting in effect and where it came from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source
+}
+
+impl<T> Setting<T> {
+    fn default(value: T) -> Self {
+        Setting { value, source: Source::Default }
+    }
+
+    /// Replaces the default with the value of the file, if the file has one
+    fn set(&mut self, value: Option<T>, file: &Path) {
+        if let Some(value) = value {
+            *self = Setting { value, source: Source::File(file.to_path_buf()) };
+        }
+    }
+}
+
+/// The layout of the configuration file
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    length: Option<u8>,
+    classes: Option<String>,
+    data_dir: Option<PathBuf>,
+    #[serde(default)]
+    kdf: KdfSection,
+    #[serde(default)]
+    clipboard: ClipboardSection,
+    #[serde(default)]
+    ui: UiSection
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KdfSection {
+    memory: Option<u32>,
+    iterations: Option<u32>,
+    parallelism: Option<u32>,
+    variant: Option<String>
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClipboardSection {
+    timeout: Option<u64>,
+    backend: Option<String>
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UiSection {
+    prompt: Option<String>,
+    session_idle_minutes: Option<u64>
+}
+
+/// The settings in effect
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Password length of new services
+    pub length: Setting<u8>,
+    /// Character types of new services
+    pub classes: Setting<Key>,
+    /// Data directory, `None` for the XDG default unless the directory in effect was filled in
+    pub data_dir: Setting<Option<PathBuf>>,
+    /// Argon2 memory cost of new users in KiB
+    pub kdf_memory: Setting<u32>,
+    /// Argon2 iterations of new users
+    pub kdf_iterations: Setting<u32>,
+    /// Argon2 lanes of new users
+    pub kdf_parallelism: Setting<u32>,
+    /// Argon2 variant of new users
+    pub kdf_variant: Setting<Variant>,
+    /// Seconds until a copied password is cleared from the clipboard, 0 keeps it
+    pub clipboard_timeout: Setting<u64>,
+    /// Where copied passwords go
+    pub clipboard_backend: Setting<ClipboardBackend>,
+    /// Prompt in front of the search line of the interactive password manager
+    pub prompt: Setting<String>,
+    /// Default minutes of being idle before a session locks
+    pub session_idle_minutes: Setting<u64>
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let profile = Generator::new();
+        Config {
+            length: Setting::default(DEFAULT_PASS_LEN),
+            classes: Setting::default(Key::default()),
+            data_dir: Setting::default(None),
+            kdf_memory: Setting::default(profile.mem_cost()),
+            kdf_iterations: Setting::default(profile.time_cost()),
+            kdf_parallelism: Setting::default(profile.lanes()),
+            kdf_variant: Setting::default(profile.variant()),
+            clipboard_timeout: Setting::default(30),
+            clipboard_backend: Setting::default(ClipboardBackend::Auto),
+            prompt: Setting::default("> ".to_string()),
+            session_idle_minutes: Setting::default(5)
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration file. A missing file is not an error and gives the defaults.
+    /// ### Params
+    /// - `filename`: path of the configuration file, usually `Paths::config_file()`
+    /// ### Returns
+    /// The settings or `Error::Config` if the file cannot be read, parsed or has invalid values
+    /// ### Side-effect
+    /// Reads the configuration file
+    pub fn load(filename: impl AsRef<Path>) -> Result<Self> {
+        let filename = filename.as_ref();
+        match std::fs::read_to_string(filename) {
+            Ok(contents) => Config::parse(&contents, filename),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(Error::Config(format!("{}: {}", filename.display(), err)))
+        }
+    }
+
+    /// Parses the contents of a configuration file
+    /// ### Params
+    /// - `contents`: the TOML of the file
+    /// - `filename`: the file the contents came from, the source of its settings
+    pub fn parse(contents: &str, filename: &Path) -> Result<Self> {
+        let invalid = |reason: String| Error::Config(format!("{}: {}", filename.display(), reason));
+        let file: ConfigFile = toml::from_str(contents).map_err(|err| invalid(err.to_string()))?;
+
+        let mut config = Config::default();
+        config.length.set(file.length, filename);
+        let classes = file.classes.map(|classes| Key::from_classes(&classes)).transpose().map_err(|err| invalid(err.to_string()))?;
+        config.classes.set(classes, filename);
+        config.data_dir.set(file.data_dir.map(Some), filename);
+        config.kdf_memory.set(file.kdf.memory, filename);
+        config.kdf_iterations.set(file.kdf.iterations, filename);
+        config.kdf_parallelism.set(file.kdf.parallelism, filename);
+        let variant = file.kdf.variant.map(|variant| Variant::from_str(&variant)).transpose()
+            .map_err(|_| invalid("kdf variant must be 'argon2d', 'argon2i' or 'argon2id'".to_string()))?;
+        config.kdf_variant.set(variant, filename);
+        config.clipboard_timeout.set(file.clipboard.timeout, filename);
+        let backend = file.clipboard.backend.map(|backend| ClipboardBackend::parse(&backend)
+            .ok_or_else(|| invalid("clipboard backend must be 'auto', 'native', 'osc52', 'stdout' or 'none'".to_string())))
+            .transpose()?;
+        config.clipboard_backend.set(backend, filename);
+        config.prompt.set(file.ui.prompt, filename);
+        config.session_idle_minutes.set(file.ui.session_idle_minutes, filename);
+
+        config.spec().validate().map_err(|err| invalid(err.to_string()))?;
+        config.profile().map_err(|err| invalid(err.to_string()))?;
+        if config.session_idle_minutes.value == 0 {
+            return Err(invalid("session_idle_minutes must be at least 1".to_string()));
+        }
+        Ok(config)
+    }
+
+    /// The recipe of new services
+    pub fn spec(&self) -> PasswordSpec {
+        PasswordSpec {
+            length: self.length.value,
+            key: self.classes.value,
+            algorithm: Algorithm::LATEST,
+            ..PasswordSpec::default()
+        }
+    }
+
+    /// The Argon2 profile of new users
+    /// ### Returns
+    /// The profile or `Error::InvalidSpec` if the KDF settings are out of range
+    pub fn profile(&self) -> Result<Generator> {
+        Generator::with_profile(self.kdf_memory.value, self.kdf_iterations.value,
+                                self.kdf_parallelism.value, self.kdf_variant.value)
+    }
+}
@@ -0,0 +1,41 @@
+//! Ways of showing a generated password to a person who has to type it in
+//! somewhere the clipboard does not reach, like a phone or a TV.
+
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+
+use crate::error::{Error, Result};
+
+/// Characters per group of `grouped()`
+pub const GROUP_SIZE: usize = 4;
+
+/// Stands in for a space in the password so it is not mistaken for a group separator
+pub const VISIBLE_SPACE: char = '\u{2423}';
+
+/// Splits the password into groups separated by spaces so it can be read aloud,
+/// e.g. `abcd efgh ij`. Spaces in the password are shown as `VISIBLE_SPACE`.
+/// ### Params
+/// - `pass`: the password
+/// - `size`: characters per group, at least 1
+pub fn grouped(pass: &str, size: usize) -> String {
+    let chars: Vec<char> = pass.chars().map(|c| if c == ' ' { VISIBLE_SPACE } else { c }).collect();
+    chars.chunks(size.max(1))
+        .map(|group| group.iter().collect::<String>())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Draws the text as a QR code with Unicode half-block characters, two modules
+/// per character cell. It is drawn light on dark for terminals with a dark background
+/// and includes the quiet zone scanners need around the code.
+/// ### Returns
+/// The lines of the code joined by newlines, or `Error::InvalidSpec` if the text
+/// does not fit in a QR code
+pub fn qr_code(text: &str) -> Result<String> {
+    let code = QrCode::new(text.as_bytes()).map_err(|err| Error::InvalidSpec(format!("cannot make a QR code: {}", err)))?;
+    Ok(code.render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .quiet_zone(true)
+        .build())
+}
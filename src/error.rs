@@ -0,0 +1,54 @@
+//! Error type returned by the library functions instead of exiting
+//! the process.
+
+use std::fmt;
+
+/// Everything that can go wrong while generating a password
+#[derive(Debug)]
+pub enum Error {
+    /// The Argon2 hashing function failed
+    Hash(argon2::Error),
+    /// The requested password properties cannot be satisfied
+    InvalidSpec(String),
+    /// A site presets file cannot be read or parsed
+    Preset(String),
+    /// A database cannot be opened or migrated to the schema of this build
+    Database(String),
+    /// The directories or files of the password manager cannot be found, made or moved
+    Io(String),
+    /// The configuration file cannot be read, parsed or has invalid values
+    Config(String),
+    /// A service cannot be renamed, deleted, aliased or merged as asked
+    Service(String),
+}
+
+/// `Result` with the library `Error` as its error type
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Hash(err) => write!(f, "error while generating Argon2 hash: {}", err),
+            Error::InvalidSpec(reason) => write!(f, "invalid password properties: {}", reason),
+            Error::Preset(reason) => write!(f, "invalid site presets: {}", reason),
+            Error::Database(reason) => write!(f, "database error: {}", reason),
+            Error::Io(reason) => write!(f, "file error: {}", reason),
+            Error::Config(reason) => write!(f, "invalid configuration: {}", reason),
+            Error::Service(reason) => write!(f, "cannot change the services: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<argon2::Error> for Error {
+    fn from(err: argon2::Error) -> Self {
+        Error::Hash(err)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Database(err.to_string())
+    }
+}
@@ -3,11 +3,33 @@
 
 use argon2::Config;
 use zeroize::Zeroize;
-use crate::characters;
+use crate::characters::{Charset, Key};
+use crate::error::{Error, Result};
+use crate::master_key::{self, MasterKey};
+use crate::passphrase::{self, PassphraseSpec, DEFAULT_WORDS, MIN_WORDS};
+use crate::pin::{self, DEFAULT_PIN_LEN, MIN_PIN_LEN};
+use crate::rules::Rules;
+use crate::stream::KeyStream;
 
 // changing this breaks fn chunk_to_arr
 const HASH_BYTES_PER_GENERATED_PASS_CHAR: u32 = 4;
 
+/// Shortest password the generator agrees to make
+pub const MIN_PASS_LEN: u8 = 4;
+
+/// Password length used when none is given
+pub const DEFAULT_PASS_LEN: u8 = 16;
+
+/// Most lanes Argon2 accepts
+const MAX_LANES: u32 = 0xFF_FFFF;
+
+/// Length of the Argon2 hash used to seed the stream of `Algorithm::V2`
+const SEED_LEN: u32 = 64;
+
+/// Most candidate passwords drawn before rules that are too hard to follow are given up on.
+/// Far more than any practical rules need, so no password that could be generated before changes.
+const MAX_CANDIDATES: u32 = 100_000;
+
 /// Converts a chunk of 4 bytes into an array of 4 bytes
 /// ONLY WORKS FOR 4 BYTE INPUT TO 4 BYTE OUTPUT
 /// ### Parameters
@@ -20,31 +42,20 @@ fn chunk_to_arr(chunk :&[u8]) -> [u8; 4] {
 
 /// Guarantees password has the property described by key
 /// ### Parameters
-/// - `pass`: password to test property of
-/// - `key` : struct that describes desired properties of the password
+/// - `pass`   : password to test property of
+/// - `key`    : struct that describes desired properties of the password
+/// - `charset`: characters left of every character type
 /// ### Returns
-/// True if and only if pass has the exact property as key. 
+/// True if and only if pass has the exact property as key.
 /// Nothing missing, nothing extra.
-fn guarantee_pass_property(pass: &str, key: &characters::Key) -> bool {
-    let re_lower = regex::Regex::new("[a-z]").unwrap();
-    let re_upper = regex::Regex::new("[A-Z]").unwrap();
-    let re_num   = regex::Regex::new("[0-9]").unwrap();
-    let re_sym   = regex::Regex::new(".*[\" !#$%&'()*+,-./:;<=>?@\\[\\\\\\]^_`{|}~].*").unwrap();
-
-    if key.lower ^ re_lower.find(pass).is_some() {return false}
-    if key.upper ^ re_upper.find(pass).is_some() {return false}
-    if key.num   ^ re_num  .find(pass).is_some() {return false}
-    if key.sym   ^ re_sym  .find(pass).is_some() {return false}
-
-    true
+fn guarantee_pass_property(pass: &str, key: &Key, charset: &Charset) -> bool {
+    charset.classes(key).iter()
+        .all(|(enabled, chars)| *enabled == pass.chars().any(|c| chars.contains(&c)))
 }
 
 /// Helper function for `generate_pass()`. For documentation see: `generate_pass()` documentation.
-fn argon2_loop(password: &mut str, salt: &[u8], config: &Config, legal_chars: &[char]) -> String {
-    let hash = argon2::hash_raw(password.as_bytes(), salt, &config).unwrap_or_else(|err| {
-        println!("Encountered error while generating Argon2 hash: {:}", err);
-        std::process::exit(1);
-    });
+fn argon2_loop(password: &mut str, salt: &[u8], config: &Config, legal_chars: &[char]) -> Result<String> {
+    let hash = argon2::hash_raw(password.as_bytes(), salt, config);
 
     password.zeroize();
 
@@ -52,12 +63,425 @@ fn argon2_loop(password: &mut str, salt: &[u8], config: &Config, legal_chars: &[
     // uses map to convert these 4-byte chunks into integers (u32)
     // uses these integers with modulo hash function to get indices of legal characters
     // and finally gets legal chars from indices and collects them into a string
-    let hash_to_pass: String = hash.chunks(HASH_BYTES_PER_GENERATED_PASS_CHAR as usize)
+    let hash_to_pass: String = hash?.chunks(HASH_BYTES_PER_GENERATED_PASS_CHAR as usize)
         .map(|chunk| u32::from_be_bytes(chunk_to_arr(chunk)))
         .map(|integer_val| integer_val % (legal_chars.len()) as u32)
         .map(|char_index| legal_chars[char_index as usize]).collect();
 
-    hash_to_pass
+    Ok(hash_to_pass)
+}
+
+/// Helper function for `Generator::generate()` with `Algorithm::V2`. Seeds a stream with a
+/// single Argon2 hash.
+fn argon2_stream(password: &mut str, salt: &[u8], config: &Config, context: &str) -> Result<KeyStream> {
+    let seed = argon2::hash_raw(password.as_bytes(), salt, config);
+
+    password.zeroize();
+
+    let mut seed = seed?;
+    let stream = KeyStream::new(&seed, context);
+    seed.zeroize();
+
+    Ok(stream)
+}
+
+/// Draws the password, passphrase or PIN of the spec from the stream
+/// ### Returns
+/// The password or `Error::InvalidSpec` if the rules are too hard to follow
+pub(crate) fn pick(stream: &mut KeyStream, spec: &PasswordSpec) -> Result<String> {
+    match spec.mode {
+        Mode::Password => pick_password(stream, &spec.charset.building_chars(&spec.key), spec),
+        Mode::Passphrase(passphrase) => Ok(passphrase::pick(stream, spec.length, &passphrase)),
+        Mode::Pin => Ok(pin::pick(stream, spec.length))
+    }
+}
+
+/// Draws candidate passwords from the stream until one has the property described by key
+/// and follows the rules
+/// ### Returns
+/// The password or `Error::InvalidSpec` if none of `MAX_CANDIDATES` candidates followed the rules
+fn pick_password(stream: &mut KeyStream, legal_chars: &[char], spec: &PasswordSpec) -> Result<String> {
+    for _ in 0..MAX_CANDIDATES {
+        let mut candidate: String = (0..spec.length)
+            .map(|_| legal_chars[stream.next_index(legal_chars.len())])
+            .collect();
+        if guarantee_pass_property(candidate.as_str(), &spec.key, &spec.charset) && spec.rules.check(&candidate) {
+            return Ok(candidate);
+        }
+        candidate.zeroize();
+    }
+    Err(Error::InvalidSpec("the rules are too hard to follow, loosen them or make the password longer".to_string()))
+}
+
+/// Version of the algorithm that turns the master password into a password.
+/// A password only reproduces with the version it was first generated with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// The original algorithm. Every character is picked as `u32 % alphabet length` from 4 bytes
+    /// of the Argon2 hash which slightly favours the characters at the start of the alphabet.
+    V1,
+    /// Every character is picked by rejection sampling from a BLAKE2b stream keyed with a
+    /// single Argon2 hash, so all characters of the alphabet are equally likely.
+    V2,
+    /// Like `V2`, but the stream is keyed with a seed derived from a `MasterKey` with keyed
+    /// BLAKE2b. The master key is the only Argon2 hash and is salted with the username, so it
+    /// can be kept for a session instead of hashing the master password for every password.
+    V3
+}
+
+impl Algorithm {
+    /// The newest algorithm
+    pub const LATEST: Algorithm = Algorithm::V3;
+
+    /// Converts the algorithm to its version number for storage
+    pub fn number(self) -> u8 {
+        match self {
+            Algorithm::V1 => 1,
+            Algorithm::V2 => 2,
+            Algorithm::V3 => 3
+        }
+    }
+
+    /// Converts a stored version number back into an algorithm
+    /// ### Returns
+    /// The algorithm or `Error::InvalidSpec` for an unknown version number
+    pub fn from_number(number: u8) -> Result<Self> {
+        match number {
+            1 => Ok(Algorithm::V1),
+            2 => Ok(Algorithm::V2),
+            3 => Ok(Algorithm::V3),
+            _ => Err(Error::InvalidSpec(format!("unknown algorithm version {}", number)))
+        }
+    }
+}
+
+/// What kind of secret is generated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Characters of the types enabled in the key
+    Password,
+    /// Words from the EFF large wordlist. Needs `Algorithm::V2` or newer.
+    Passphrase(PassphraseSpec),
+    /// Digits only, weak PINs are never generated. Needs `Algorithm::V2` or newer.
+    Pin
+}
+
+impl Mode {
+    /// Smallest length the generator accepts in this mode
+    pub fn min_length(self) -> u8 {
+        match self {
+            Mode::Password => MIN_PASS_LEN,
+            Mode::Passphrase(_) => MIN_WORDS,
+            Mode::Pin => MIN_PIN_LEN
+        }
+    }
+
+    /// Length used when switching to this mode
+    pub fn default_length(self) -> u8 {
+        match self {
+            Mode::Password => DEFAULT_PASS_LEN,
+            Mode::Passphrase(_) => DEFAULT_WORDS,
+            Mode::Pin => DEFAULT_PIN_LEN
+        }
+    }
+}
+
+/// Properties of the password to generate i.e. its length, the
+/// types of characters it is made of and the algorithm generating it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PasswordSpec {
+    /// Number of characters, number of words for passphrases or number of digits for PINs
+    pub length: u8,
+    /// Character types of passwords, unused by passphrases and PINs
+    pub key: Key,
+    /// Characters of the types that may be used, unused by passphrases and PINs
+    pub charset: Charset,
+    /// Policy the password follows, unused by passphrases and PINs. Needs `Algorithm::V2` or newer.
+    pub rules: Rules,
+    pub algorithm: Algorithm,
+    pub mode: Mode
+}
+
+impl PasswordSpec {
+    /// Makes a password spec from a length and a key using `Algorithm::V1`
+    /// ### Returns
+    /// A `PasswordSpec` with the given properties. Use `validate()` to check it.
+    pub fn new(length: u8, key: Key) -> Self {
+        PasswordSpec { length, key, charset: Charset::default(),
+                       rules: Rules::default(), algorithm: Algorithm::V1, mode: Mode::Password }
+    }
+
+    /// Makes a passphrase spec from a number of words using the newest algorithm
+    /// ### Returns
+    /// A `PasswordSpec` with the given properties. Use `validate()` to check it.
+    pub fn passphrase(words: u8, passphrase: PassphraseSpec) -> Self {
+        PasswordSpec {
+            length: words,
+            key: Key::default(),
+            charset: Charset::default(),
+            rules: Rules::default(),
+            algorithm: Algorithm::LATEST,
+            mode: Mode::Passphrase(passphrase)
+        }
+    }
+
+    /// Makes a PIN spec from a number of digits using the newest algorithm
+    /// ### Returns
+    /// A `PasswordSpec` with the given properties. Use `validate()` to check it.
+    pub fn pin(digits: u8) -> Self {
+        PasswordSpec {
+            length: digits,
+            key: Key::default(),
+            charset: Charset::default(),
+            rules: Rules::default(),
+            algorithm: Algorithm::LATEST,
+            mode: Mode::Pin
+        }
+    }
+
+    /// Changes the mode. Switching to another kind of mode resets the length to the
+    /// default of the new mode, and moves passphrases and PINs off `Algorithm::V1`.
+    pub fn set_mode(&mut self, mode: Mode) {
+        if std::mem::discriminant(&self.mode) != std::mem::discriminant(&mode) {
+            self.length = mode.default_length();
+            if mode != Mode::Password && self.algorithm == Algorithm::V1 {
+                self.algorithm = Algorithm::LATEST;
+            }
+        }
+        self.mode = mode;
+    }
+
+    /// Tells the stream what it is used for so specs differing in anything but formatting
+    /// get unrelated streams, e.g. a longer password does not start with a shorter one.
+    /// Narrowed down character sets add their characters and rules add themselves,
+    /// the defaults add nothing.
+    pub(crate) fn stream_context(&self) -> String {
+        match self.mode {
+            Mode::Password => {
+                let mut context = format!("password/{}/{}", self.length, self.key.to_classes());
+                if self.charset != Charset::default() {
+                    let legal_chars: String = self.charset.building_chars(&self.key).into_iter().collect();
+                    context = format!("{}/{}", context, legal_chars);
+                }
+                if self.rules != Rules::default() {
+                    context = format!("{}/rules:{}", context, self.rules);
+                }
+                context
+            },
+            Mode::Passphrase(_) => format!("passphrase/{}", self.length),
+            Mode::Pin => format!("pin/{}", self.length)
+        }
+    }
+
+    /// Checks that a password can be generated with this spec
+    /// ### Returns
+    /// `Err(Error::InvalidSpec)` if the length is below the minimum of the mode
+    /// (`MIN_PASS_LEN`, `MIN_WORDS` or `MIN_PIN_LEN`), no character type is
+    /// enabled for a password, the charset leaves an enabled type without characters
+    /// or the rules cannot be followed (or are used with `Algorithm::V1`). Passphrases and PINs also need an algorithm
+    /// other than `Algorithm::V1`.
+    pub fn validate(&self) -> Result<()> {
+        match self.mode {
+            Mode::Password => {
+                if self.length < MIN_PASS_LEN {
+                    return Err(Error::InvalidSpec(format!("length must be >= {}", MIN_PASS_LEN)));
+                }
+                if !(self.key.upper || self.key.lower || self.key.num || self.key.sym) {
+                    return Err(Error::InvalidSpec("at least one character type must be enabled".to_string()));
+                }
+                let classes = self.charset.classes(&self.key);
+                if classes.iter().any(|(enabled, chars)| *enabled && chars.is_empty()) {
+                    return Err(Error::InvalidSpec("every enabled character type needs a character that is not excluded".to_string()));
+                }
+                if self.rules != Rules::default() {
+                    if self.algorithm == Algorithm::V1 {
+                        return Err(Error::InvalidSpec("rules need algorithm version 2 or newer".to_string()));
+                    }
+                    self.rules.validate(self.length, classes.map(|(enabled, chars)| if enabled { chars.len() } else { 0 }))?;
+                }
+            },
+            Mode::Passphrase(_) => {
+                if self.length < MIN_WORDS {
+                    return Err(Error::InvalidSpec(format!("passphrases need at least {} words", MIN_WORDS)));
+                }
+                if self.algorithm == Algorithm::V1 {
+                    return Err(Error::InvalidSpec("passphrases need algorithm version 2 or newer".to_string()));
+                }
+            },
+            Mode::Pin => {
+                if self.length < MIN_PIN_LEN {
+                    return Err(Error::InvalidSpec(format!("PINs need at least {} digits", MIN_PIN_LEN)));
+                }
+                if self.algorithm == Algorithm::V1 {
+                    return Err(Error::InvalidSpec("PINs need algorithm version 2 or newer".to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for PasswordSpec {
+    /// Length `DEFAULT_PASS_LEN` with every character type enabled
+    fn default() -> Self {
+        PasswordSpec::new(DEFAULT_PASS_LEN, Key::default())
+    }
+}
+
+/// The stateless password generator. Holds the Argon2 cost parameters
+/// (the KDF profile) used for every password it generates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Generator {
+    mem_cost: u32,
+    time_cost: u32,
+    lanes: u32,
+    variant: argon2::Variant
+}
+
+impl Generator {
+    /// Makes a generator with the recommended Argon2 parameters. These are
+    /// the parameters every existing password was generated with.
+    pub fn new() -> Self {
+        // Recommended numbers. For more information: look into argon2
+        Generator {
+            mem_cost: 16384,
+            time_cost: 4,
+            lanes: 8,
+            variant: argon2::Variant::Argon2id
+        }
+    }
+
+    /// Makes a generator with custom Argon2 parameters. Passwords depend on
+    /// them, so a profile has to stay the same for as long as its passwords are used.
+    /// ### Parameters
+    /// - `mem_cost` : memory in KiB, at least 8 per lane
+    /// - `time_cost`: number of iterations, at least 1
+    /// - `lanes`    : degree of parallelism, at least 1
+    /// - `variant`  : Argon2d, Argon2i or Argon2id
+    /// ### Returns
+    /// The generator or `Error::InvalidSpec` if Argon2 does not accept the parameters
+    pub fn with_profile(mem_cost: u32, time_cost: u32, lanes: u32, variant: argon2::Variant) -> Result<Self> {
+        if time_cost == 0 || lanes == 0 || lanes > MAX_LANES || mem_cost < 8 * lanes {
+            return Err(Error::InvalidSpec(format!(
+                "Argon2 needs at least 1 iteration, 1 to {} lanes and 8 KiB of memory per lane", MAX_LANES)));
+        }
+        Ok(Generator { mem_cost, time_cost, lanes, variant })
+    }
+
+    /// Memory in KiB
+    pub fn mem_cost(&self) -> u32 {
+        self.mem_cost
+    }
+
+    /// Number of iterations
+    pub fn time_cost(&self) -> u32 {
+        self.time_cost
+    }
+
+    /// Degree of parallelism
+    pub fn lanes(&self) -> u32 {
+        self.lanes
+    }
+
+    pub fn variant(&self) -> argon2::Variant {
+        self.variant
+    }
+
+    /// Argon2 configuration with the parameters of the generator
+    /// ### Parameters
+    /// - `hash_length`: number of bytes of the hash
+    pub fn config(&self, hash_length: u32) -> Config<'static> {
+        argon2::Config {
+            variant: self.variant,
+            version: argon2::Version::Version13,
+            mem_cost: self.mem_cost,
+            time_cost: self.time_cost,
+            lanes: self.lanes,
+            thread_mode: argon2::ThreadMode::Parallel,
+            secret: &[],
+            ad: &[],
+            hash_length
+        }
+    }
+
+    /// Measures how long the generator takes to derive one password
+    /// ### Returns
+    /// The time one Argon2 hash takes or an `Error` if hashing fails
+    pub fn measure(&self) -> Result<std::time::Duration> {
+        let start = std::time::Instant::now();
+        argon2::hash_raw(b"benchmark password", b"benchmark salt", &self.config(SEED_LEN))?;
+        Ok(start.elapsed())
+    }
+
+    /// Stretches the master password into the master key of `Algorithm::V3`
+    /// ### Parameters
+    /// - `master`  : the user's master password
+    /// - `username`: the user, salts the key
+    /// ### Returns
+    /// The master key or an `Error` if hashing fails
+    /// ### Side-effect
+    /// Zeroizes the given master password, also when an error is returned.
+    pub fn master_key(&self, master: &mut str, username: &str) -> Result<MasterKey> {
+        let salt = format!("{}{}", master_key::SALT_PREFIX, username);
+        let hash = argon2::hash_raw(master.as_bytes(), salt.as_bytes(), &self.config(master_key::KEY_LEN as u32));
+        master.zeroize();
+        Ok(MasterKey::new(&mut hash?))
+    }
+
+    /// Generates a password for the given salt parts
+    /// ### Parameters
+    /// - `master`    : the user's master password
+    /// - `salt_parts`: pieces of the salt (username, service title, password number, ...),
+    ///   concatenated in order. `Algorithm::V3` salts the master key with the first part
+    ///   and the service with the others.
+    /// - `spec`      : length and character types of the password
+    /// ### Returns
+    /// The generated password or an `Error` if the spec is invalid or hashing fails
+    /// ### Side-effect
+    /// Zeroizes the given master password, also when an error is returned.
+    pub fn generate(&self, master: &mut str, salt_parts: &[&str], spec: &PasswordSpec) -> Result<String> {
+        if let Err(err) = spec.validate() {
+            master.zeroize();
+            return Err(err);
+        }
+        if spec.algorithm == Algorithm::V3 {
+            return match salt_parts.split_first() {
+                Some((username, service_parts)) => self.master_key(master, username)?.generate(service_parts, spec),
+                None => {
+                    master.zeroize();
+                    Err(Error::InvalidSpec("algorithm version 3 needs the username as first salt part".to_string()))
+                }
+            };
+        }
+        let salt = salt_parts.concat();
+        self.generate_raw(master, salt.as_bytes(), spec)
+    }
+
+    /// Generates a password of `Algorithm::V1` or `Algorithm::V2` from an already assembled salt.
+    /// For documentation see `generate()`.
+    fn generate_raw(&self, master: &mut str, salt: &[u8], spec: &PasswordSpec) -> Result<String> {
+        if spec.algorithm != Algorithm::V1 {
+            let config = self.config(SEED_LEN);
+            let mut stream = argon2_stream(master, salt, &config, &spec.stream_context())?;
+            return pick(&mut stream, spec);
+        }
+
+        let config = self.config((spec.length) as u32 * HASH_BYTES_PER_GENERATED_PASS_CHAR);
+        let legal_chars = spec.charset.building_chars(&spec.key);
+        let mut unguaranteed_pass = argon2_loop(master, salt, &config, &legal_chars)?;
+        loop {
+            if guarantee_pass_property((unguaranteed_pass).as_str(), &spec.key, &spec.charset) {
+                return Ok(unguaranteed_pass);
+            }
+            unguaranteed_pass = argon2_loop(&mut unguaranteed_pass, salt, &config, &legal_chars)?;
+        }
+    }
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator::new()
+    }
 }
 
 /// Generates a password for given service
@@ -67,13 +491,13 @@ fn argon2_loop(password: &mut str, salt: &[u8], config: &Config, legal_chars: &[
 /// - `pass_len`: Length of password that needs to be generated
 /// - `key`     : Types of characters that need to be present in generated password
 /// ### Returns
-/// The generated password
+/// The generated password or an `Error` if the key is invalid or hashing fails
 /// ### Side-effect
 /// Zeroizes the given password.
-/// ### Panics
-/// None. But the function exits the execution if the hashing function fails
 /// ## Usage:
 /// ```
+/// use project_stella::{characters, generator};
+///
 /// let key = characters::Key{upper: true, 
 ///     lower: true, 
 ///     num: true, 
@@ -82,29 +506,14 @@ fn argon2_loop(password: &mut str, salt: &[u8], config: &Config, legal_chars: &[
 /// 
 /// let mut password = "Hello".to_string();
 /// let salt = b"randomsalt";
-/// let pass = generator::generate_pass(&mut password, salt, 16, key);
-/// assert_eq!(pass, "1pXkcUb4LgtFCkXJ".to_string())
+/// let pass = generator::generate_pass(&mut password, salt, 16, key).unwrap();
+/// assert_eq!(pass, "ARB5vSFuXF10zDZL".to_string())
 /// ```
-pub fn generate_pass(password: &mut str, salt: &[u8], pass_len: u8, key: characters::Key) -> String {
-    // Recommended numbers. For more information: look into argon2
-    let config = argon2::Config {
-        variant: argon2::Variant::Argon2id,
-        version: argon2::Version::Version13,
-        mem_cost: 16384,
-        time_cost: 4,
-        lanes: 8,
-        thread_mode: argon2::ThreadMode::Parallel,
-        secret: &[],
-        ad: &[],
-        hash_length: (pass_len) as u32 * HASH_BYTES_PER_GENERATED_PASS_CHAR
-    };
-
-    let legal_chars = characters::get_pass_building_chars(&key);
-    let mut unguaranteed_pass = argon2_loop(password, salt, &config, &legal_chars);
-    loop {
-        if guarantee_pass_property((unguaranteed_pass).as_str(), &key) {
-            return unguaranteed_pass;
-        }
-        unguaranteed_pass = argon2_loop(&mut unguaranteed_pass, salt, &config, &legal_chars);
+pub fn generate_pass(password: &mut str, salt: &[u8], pass_len: u8, key: Key) -> Result<String> {
+    let spec = PasswordSpec::new(pass_len, key);
+    if let Err(err) = spec.validate() {
+        password.zeroize();
+        return Err(err);
     }
+    Generator::new().generate_raw(password, salt, &spec)
 }
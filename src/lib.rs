@@ -0,0 +1,41 @@
+//! Library half of the stellar password manager. Exposes the stateless
+//! Argon2 password generator and the user and service storage so they can
+//! be used outside of the terminal application.
+//!
+//! ## Usage:
+//! ```
+//! use project_stella::{Generator, PasswordSpec, Key};
+//!
+//! let spec = PasswordSpec::new(16, Key::default());
+//! let mut master = "Hello".to_string();
+//! let pass = Generator::new().generate(&mut master, &["alice", "github", "1"], &spec).unwrap();
+//! assert_eq!(pass.len(), 16);
+//! ```
+
+pub mod auth;
+pub mod characters;
+pub mod config;
+pub mod display;
+pub mod error;
+pub mod generator;
+pub mod master_key;
+pub mod passphrase;
+pub mod password_change;
+pub mod paths;
+pub mod pin;
+pub mod presets;
+pub mod rules;
+pub mod schema;
+pub mod search;
+pub mod service_db_actions;
+mod stream;
+
+pub use argon2::Variant;
+pub use characters::{Charset, Key};
+pub use config::Config;
+pub use error::{Error, Result};
+pub use generator::{Algorithm, Generator, Mode, PasswordSpec};
+pub use master_key::MasterKey;
+pub use passphrase::PassphraseSpec;
+pub use paths::Paths;
+pub use rules::Rules;
@@ -1,15 +1,104 @@
-use auth::auth_user;
-use user_inputs::{get_visible_input_prompt, get_hidden_input_prompt};
+use std::path::Path;
+use std::sync::OnceLock;
+use user_inputs::{auth_user, get_visible_input_prompt, get_hidden_input_prompt};
 use zeroize::Zeroize;
+use project_stella::{paths, Config, Paths};
+use project_stella::config::{ClipboardBackend, Setting, Source};
 
-mod characters;
-mod generator;
 mod user_inputs;
-mod service_cli;
-mod service_db_actions;
-mod auth;
+mod commands;
+mod session;
+mod reveal;
+mod clipboard;
+mod clipboard_timer;
+mod tui;
+
+/// Directories of the data and configuration files, set once at startup
+static PATHS: OnceLock<Paths> = OnceLock::new();
+
+/// Settings of the configuration file, set once at startup
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// The directories the password manager keeps its files in
+pub fn paths() -> &'static Paths {
+    PATHS.get().expect("paths are set at startup")
+}
+
+/// The settings in effect
+pub fn config() -> &'static Config {
+    CONFIG.get().expect("config is loaded at startup")
+}
+
+/// Loads the configuration, finds the data and configuration directories, creates them
+/// and moves the files builds before them left in the working directory
+/// ### Params
+/// - `data_dir`: the `--data-dir` given on the command line
+/// ### Side-effect
+/// Reads the configuration file, creates directories and moves files, tells about moved
+/// files on stderr. Exits the process if the configuration is invalid or the directories
+/// cannot be found or made.
+fn init(data_dir: Option<&Path>) {
+    let init = || -> project_stella::Result<(Paths, Config)> {
+        let mut config = Config::load(Paths::resolve(data_dir, None)?.config_file())?;
+        let paths = Paths::resolve(data_dir, config.data_dir.value.as_deref())?;
+        let source = if data_dir.is_some() {
+            Source::CommandLine("--data-dir".to_string())
+        } else if std::env::var_os(paths::HOME_VAR).is_some_and(|home| !home.is_empty()) {
+            Source::Environment(paths::HOME_VAR.to_string())
+        } else {
+            config.data_dir.source.clone()
+        };
+        config.data_dir = Setting { value: Some(paths.data_dir.clone()), source };
+
+        paths.create()?;
+        if let Ok(cwd) = std::env::current_dir() {
+            let (moved, left) = paths.move_legacy_files(&cwd)?;
+            for path in moved {
+                eprintln!("Moved {} from the working directory to {}", path.file_name().unwrap_or_default().to_string_lossy(), path.display());
+            }
+            for path in left {
+                eprintln!("Ignoring {}, the data directory already has one", path.display());
+            }
+        }
+        Ok((paths, config))
+    };
+    match init() {
+        Ok((paths, config)) => {
+            let _ = PATHS.set(paths);
+            let _ = CONFIG.set(config);
+        },
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
 
 fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // --data-dir comes before the command
+    let data_dir = match args.first().map(String::as_str) {
+        Some("--data-dir") if args.len() >= 2 => {
+            let data_dir = args.remove(1);
+            args.remove(0);
+            Some(data_dir)
+        },
+        Some("--data-dir") => {
+            eprintln!("error: missing value for '--data-dir'");
+            std::process::exit(1);
+        },
+        _ => None
+    };
+    init(data_dir.as_deref().map(Path::new));
+    // n_curses draws Unicode (QR codes, the visible space) only in the locale of the terminal,
+    // and only if it is set before the first screen is made
+    ncurses::setlocale(ncurses::LcCategory::all, "");
+
+    if !args.is_empty() {
+        commands::run(&args);
+        return;
+    }
+
     println!("Launched stellar password manager.");
 
     // Authorize
@@ -24,10 +113,13 @@ fn main() {
     // zeroize password
     password.zeroize();
 
-    println!("Logged in as: {}\nType in 'help' for available commands.", username);
+    println!("Logged in as: {}", username);
     // start clipboard (clipboard values disappear when clipboard is dropped so DON'T DROP IT TOO SOON)
-    let mut clipboard = arboard::Clipboard::new().unwrap();
+    let clipboard = clipboard::open(config().clipboard_backend.value).unwrap_or_else(|reason| {
+        println!("{}, passwords are not copied.", reason);
+        clipboard::open(ClipboardBackend::None).unwrap()
+    });
 
-    // initiate main user input loop
-    user_inputs::start_user_input_loop(&username.as_str(), &mut clipboard);
+    // take over the terminal until the user quits
+    tui::run(username.as_str(), clipboard);
 }
@@ -0,0 +1,108 @@
+//! The master key of `Algorithm::V3`. The master password is stretched with
+//! Argon2 once into the key, and every service password is derived from the
+//! key with a fast keyed BLAKE2b, so a session only pays for Argon2 once.
+
+use blake2b_simd::Params;
+use zeroize::Zeroize;
+
+use crate::error::{Error, Result};
+use crate::generator::{self, Algorithm, PasswordSpec};
+use crate::stream::KeyStream;
+
+/// Length of the master key and of the seeds derived from it
+pub(crate) const KEY_LEN: usize = 64;
+
+/// Separates deriving seeds from any other use of the master key
+const PERSONAL: &[u8; 16] = b"stellar-seed-v3\0";
+
+/// Prefix of the Argon2 salt of the master key, followed by the username
+pub(crate) const SALT_PREFIX: &str = "stellar-master-key:";
+
+/// A master password stretched with Argon2. The key is kept out of swap where
+/// the system allows it and zeroized when it is dropped.
+pub struct MasterKey {
+    key: Box<[u8; KEY_LEN]>
+}
+
+impl MasterKey {
+    /// Takes over a freshly stretched key
+    /// ### Side-effect
+    /// Zeroizes `bytes` and locks the memory of the key
+    pub(crate) fn new(bytes: &mut [u8]) -> Self {
+        let mut key = Box::new([0; KEY_LEN]);
+        key.copy_from_slice(bytes);
+        bytes.zeroize();
+        lock_memory(key.as_ref());
+        MasterKey { key }
+    }
+
+    /// Derives the seed of a service
+    fn seed(&self, service_salt: &[u8]) -> [u8; KEY_LEN] {
+        let hash = Params::new()
+            .hash_length(KEY_LEN)
+            .key(self.key.as_ref())
+            .personal(PERSONAL)
+            .hash(service_salt);
+        let mut seed = [0; KEY_LEN];
+        seed.copy_from_slice(hash.as_bytes());
+        seed
+    }
+
+    /// Generates a password of `Algorithm::V3` without running Argon2
+    /// ### Parameters
+    /// - `salt_parts`: pieces of the service salt (service title, password number, ...),
+    ///   concatenated in order. Unlike `Generator::generate()` without the username,
+    ///   which is part of the key.
+    /// - `spec`      : properties of the password
+    /// ### Returns
+    /// The generated password or `Error::InvalidSpec` if the spec is invalid or
+    /// uses another algorithm, which needs the master password
+    pub fn generate(&self, salt_parts: &[&str], spec: &PasswordSpec) -> Result<String> {
+        spec.validate()?;
+        if spec.algorithm != Algorithm::V3 {
+            return Err(Error::InvalidSpec(format!(
+                "algorithm version {} needs the master password", spec.algorithm.number())));
+        }
+        let mut seed = self.seed(salt_parts.concat().as_bytes());
+        let mut stream = KeyStream::new(&seed, &spec.stream_context());
+        seed.zeroize();
+        generator::pick(&mut stream, spec)
+    }
+}
+
+impl std::fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+impl Drop for MasterKey {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        unlock_memory(self.key.as_ref());
+    }
+}
+
+/// Asks the system to keep the key out of swap. Best effort, the key is
+/// still zeroized if locking is not allowed.
+#[cfg(unix)]
+fn lock_memory(key: &[u8; KEY_LEN]) {
+    // SAFETY: the pointer and length describe the key, which lives until unlock_memory
+    unsafe {
+        libc::mlock(key.as_ptr() as *const libc::c_void, KEY_LEN);
+    }
+}
+
+#[cfg(unix)]
+fn unlock_memory(key: &[u8; KEY_LEN]) {
+    // SAFETY: the pointer and length describe the key locked by lock_memory
+    unsafe {
+        libc::munlock(key.as_ptr() as *const libc::c_void, KEY_LEN);
+    }
+}
+
+#[cfg(not(unix))]
+fn lock_memory(_key: &[u8; KEY_LEN]) {}
+
+#[cfg(not(unix))]
+fn unlock_memory(_key: &[u8; KEY_LEN]) {}
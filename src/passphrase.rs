@@ -0,0 +1,82 @@
+//! Diceware-style passphrases made of words from the EFF large wordlist.
+//!
+//! The wordlist is by the Electronic Frontier Foundation
+//! (<https://www.eff.org/dice>) and is licensed under CC BY 3.0 US.
+
+use std::sync::OnceLock;
+use zeroize::Zeroize;
+
+use crate::stream::KeyStream;
+
+/// The EFF large wordlist as published: a dice roll and a word on each line
+const EFF_LARGE_WORDLIST: &str = include_str!("wordlists/eff_large_wordlist.txt");
+
+/// Fewest words the generator agrees to put in a passphrase
+pub const MIN_WORDS: u8 = 4;
+
+/// Number of words used when switching to passphrases
+pub const DEFAULT_WORDS: u8 = 6;
+
+/// How passphrase words are put together. Only changes the formatting,
+/// except for `digit` which also draws the digit and its position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassphraseSpec {
+    /// Put between words, `None` to run the words together
+    pub separator: Option<char>,
+    /// Upper case the first letter of every word
+    pub capitalize: bool,
+    /// Append a digit to one of the words
+    pub digit: bool
+}
+
+impl Default for PassphraseSpec {
+    /// Lower case words separated by spaces without a digit
+    fn default() -> Self {
+        PassphraseSpec {
+            separator: Some(' '),
+            capitalize: false,
+            digit: false
+        }
+    }
+}
+
+/// The words of the wordlist in dice roll order
+fn words() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    WORDS.get_or_init(|| {
+        EFF_LARGE_WORDLIST.lines()
+            .filter_map(|line| line.split('\t').nth(1))
+            .collect()
+    })
+}
+
+/// Draws a passphrase from the stream
+/// ### Parameters
+/// - `stream`: stream seeded from the master password and salt
+/// - `word_count`: number of words in the passphrase
+/// - `spec`  : how the words are put together
+/// ### Returns
+/// The passphrase
+pub(crate) fn pick(stream: &mut KeyStream, word_count: u8, spec: &PassphraseSpec) -> String {
+    let words = words();
+    let mut picked: Vec<String> = (0..word_count)
+        .map(|_| words[stream.next_index(words.len())].to_string())
+        .collect();
+
+    if spec.capitalize {
+        // the words of the list are all ASCII
+        for word in picked.iter_mut() {
+            word[..1].make_ascii_uppercase();
+        }
+    }
+    if spec.digit {
+        let index = stream.next_index(picked.len());
+        let digit = stream.next_index(10);
+        picked[index].push_str(&digit.to_string());
+    }
+
+    let separator = spec.separator.map(String::from).unwrap_or_default();
+    let passphrase = picked.join(&separator);
+    picked.zeroize();
+    passphrase
+}
@@ -0,0 +1,139 @@
+//! Tracks changes of the master password. Every service generated with the old
+//! password has to be changed on its website, so a change remembers which
+//! services have been migrated to the new password and which are still pending.
+//! The hash of the old password is kept until every service is migrated so the
+//! old passwords can be shown again after the password manager is restarted.
+
+use rusqlite::{params, Connection, Result};
+
+use crate::auth;
+
+/// Creates the tables tracking password changes if they do not exist, part of
+/// the first schema version of the authentication database
+/// ### Parameters
+/// - `conn` : Connection to the authentication database
+pub(crate) fn create_tables(conn: &Connection) -> Result<()> {
+    conn.execute("CREATE TABLE IF NOT EXISTS password_changes(
+        username TEXT NOT NULL UNIQUE,
+        old_hash TEXT NOT NULL
+    )", ())?;
+    conn.execute("CREATE TABLE IF NOT EXISTS migrations(
+        username TEXT NOT NULL,
+        title TEXT NOT NULL,
+        migrated INTEGER NOT NULL DEFAULT 0,
+        UNIQUE(username, title)
+    )", ())?;
+    Ok(())
+}
+
+/// A service whose password has to be changed after the master password changed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Migration {
+    pub title: String,
+    /// Whether the user changed the password on the website
+    pub migrated: bool
+}
+
+/// Checks whether the user changed the master password and has not migrated every service yet
+/// ### Parameters
+/// - `conn`    : Connection to the authentication database
+/// - `username`: username to look up
+pub fn is_pending(conn: &Connection, username: &str) -> bool {
+    let query = "SELECT username FROM password_changes WHERE username = ?1";
+    conn.query_row(query, params![username], |row| row.get::<_, String>(0)).is_ok()
+}
+
+/// Changes the master password of a user and marks the services as pending
+/// ### Parameters
+/// - `conn`        : Connection to the authentication database
+/// - `username`    : username of the user
+/// - `old_password`: the current password, checked before anything is changed
+/// - `new_password`: the password replacing it
+/// - `titles`      : services generated with the old password
+/// ### Returns
+/// false if the old password does not match or an earlier change is still pending,
+/// true if the password was changed
+/// ### Side-effects
+/// Writes to the authentication database in a single transaction
+pub fn change_password(conn: &mut Connection, username: &str, old_password: &str, new_password: &str,
+                       titles: &[String]) -> bool {
+    if is_pending(conn, username) || !auth::verify_user(conn, username, old_password) {
+        return false;
+    }
+
+    record_change(conn, username, new_password, titles).unwrap_or(false)
+}
+
+/// Keeps the old hash, marks the services as pending and sets the new password, all or nothing
+fn record_change(conn: &mut Connection, username: &str, new_password: &str, titles: &[String]) -> Result<bool> {
+    let tx = conn.transaction()?;
+    tx.execute("INSERT INTO password_changes (username, old_hash)
+                SELECT username, password_hash FROM auth WHERE username = ?1", params![username])?;
+    for title in titles {
+        tx.execute("INSERT OR IGNORE INTO migrations (username, title) VALUES (?1, ?2)", params![username, title])?;
+    }
+    if !auth::set_password(&tx, username, new_password) {
+        return Ok(false);
+    }
+    tx.commit()?;
+    Ok(true)
+}
+
+/// Checks the password the user had before the pending change
+/// ### Returns
+/// true if a change is pending and the password matches the old hash
+pub fn verify_old_password(conn: &Connection, username: &str, password: &str) -> bool {
+    let query = "SELECT old_hash FROM password_changes WHERE username = ?1";
+    match conn.query_row(query, params![username], |row| row.get::<_, String>(0)) {
+        Ok(hash) => argon2::verify_encoded(&hash, password.as_bytes()).unwrap_or(false),
+        Err(_) => false
+    }
+}
+
+/// Reads the services of the pending change
+/// ### Returns
+/// Every service ordered by title, migrated or not
+/// ### Side-effect
+/// Reads from database
+pub fn read_migrations(conn: &Connection, username: &str) -> Result<Vec<Migration>> {
+    let query = "SELECT title, migrated FROM migrations WHERE username = ?1 ORDER BY title";
+    let migrations: Vec<Migration> = conn
+        .prepare(query)?
+        .query_map(params![username], |row| Ok(Migration { title: row.get(0)?, migrated: row.get(1)? }))?
+        .collect::<Result<_>>()?;
+    Ok(migrations)
+}
+
+/// Remembers that the password of a service was changed on its website
+/// ### Side-effect
+/// Writes to database
+pub fn mark_migrated(conn: &Connection, username: &str, title: &str) {
+    let query = "UPDATE migrations SET migrated = 1 WHERE username = ?1 AND title = ?2";
+    let _ = conn.execute(query, params![username, title]);
+}
+
+/// Moves the pending change of a service to its new title after it was renamed or merged.
+/// If the new title is pending itself the entry of the old title is dropped.
+/// ### Side-effect
+/// Writes to database
+pub fn rename_service(conn: &Connection, username: &str, title: &str, new_title: &str) -> Result<()> {
+    conn.execute("UPDATE OR IGNORE migrations SET title = ?3 WHERE username = ?1 AND title = ?2", params![username, title, new_title])?;
+    conn.execute("DELETE FROM migrations WHERE username = ?1 AND title = ?2", params![username, title])?;
+    Ok(())
+}
+
+/// Ends the pending change once every service is migrated, forgetting the old password hash
+/// ### Returns
+/// true if no change is pending anymore, false if services are still pending
+/// ### Side-effect
+/// Writes to database
+pub fn finish(conn: &Connection, username: &str) -> bool {
+    let query = "SELECT COUNT(*) FROM migrations WHERE username = ?1 AND migrated = 0";
+    let pending: u32 = conn.query_row(query, params![username], |row| row.get(0)).unwrap_or(1);
+    if pending > 0 {
+        return false;
+    }
+    let _ = conn.execute("DELETE FROM migrations WHERE username = ?1", params![username]);
+    let _ = conn.execute("DELETE FROM password_changes WHERE username = ?1", params![username]);
+    true
+}
@@ -0,0 +1,178 @@
+//! Where the password manager keeps its files, following the XDG base directories:
+//! - data (`auth.db` and `service_records.db`) in `$XDG_DATA_HOME/stellar`,
+//!   `~/.local/share/stellar` if it is not set. `data_dir` in the configuration file,
+//!   `STELLAR_HOME` and `--data-dir` override it, in increasing precedence.
+//! - configuration (`config.toml` and `presets.toml`) in `$XDG_CONFIG_HOME/stellar`, `~/.config/stellar`
+//!   if it is not set.
+//!
+//! Builds before these directories opened their databases in the current working
+//! directory, `move_legacy_files()` moves them to their new place on the first start.
+
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::auth::AUTH_DB;
+use crate::config::CONFIG_FILE;
+use crate::error::{Error, Result};
+use crate::presets::USER_PRESETS;
+use crate::service_db_actions::SERVICES_DB;
+
+/// Name of the directory inside the XDG base directories
+const APP_DIR: &str = "stellar";
+
+/// Environment variable overriding the data directory
+pub const HOME_VAR: &str = "STELLAR_HOME";
+
+/// File in the data directory telling that `move_legacy_files()` already ran
+const LEGACY_MARKER: &str = ".legacy-files-checked";
+
+/// Files sqlite keeps next to a database while it is written
+const SQLITE_SIDE_FILES: [&str; 3] = ["-journal", "-wal", "-shm"];
+
+/// The directories of the password manager
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Paths {
+    pub data_dir: PathBuf,
+    pub config_dir: PathBuf
+}
+
+impl Paths {
+    /// Finds the directories from the environment of the process
+    /// ### Params
+    /// - `data_dir`: the `--data-dir` given on the command line, overrides everything else
+    /// - `configured`: the `data_dir` of the configuration file, used unless `STELLAR_HOME` is set
+    /// ### Returns
+    /// The directories or `Error::Io` if neither the XDG variables nor `HOME` are set
+    pub fn resolve(data_dir: Option<&Path>, configured: Option<&Path>) -> Result<Self> {
+        Paths::from_env(data_dir, configured, |name| std::env::var_os(name).map(PathBuf::from))
+    }
+
+    /// Finds the directories like `resolve()` with the environment variables read by `env`.
+    /// Empty variables count as not set, like the XDG specification asks.
+    pub fn from_env(data_dir: Option<&Path>, configured: Option<&Path>, env: impl Fn(&str) -> Option<PathBuf>) -> Result<Self> {
+        let env = |name: &str| env(name).filter(|value| !value.as_os_str().is_empty());
+        let home = || env("HOME").or_else(|| env("APPDATA"))
+            .ok_or_else(|| Error::Io("cannot find the home directory, set HOME or STELLAR_HOME".to_string()));
+
+        let data_dir = data_dir.map(Path::to_path_buf)
+            .or_else(|| env(HOME_VAR))
+            .or_else(|| configured.map(Path::to_path_buf));
+        let data_dir = match data_dir {
+            Some(data_dir) => data_dir,
+            None => env("XDG_DATA_HOME").map_or_else(|| home().map(|home| home.join(".local/share")), Ok)?.join(APP_DIR)
+        };
+        let config_dir = env("XDG_CONFIG_HOME").map_or_else(|| home().map(|home| home.join(".config")), Ok)?.join(APP_DIR);
+        Ok(Paths { data_dir, config_dir })
+    }
+
+    /// Path of the authentication database
+    pub fn auth_db(&self) -> PathBuf {
+        self.data_dir.join(AUTH_DB)
+    }
+
+    /// Path of the service records database
+    pub fn services_db(&self) -> PathBuf {
+        self.data_dir.join(SERVICES_DB)
+    }
+
+    /// Path of the configuration file
+    pub fn config_file(&self) -> PathBuf {
+        self.config_dir.join(CONFIG_FILE)
+    }
+
+    /// Path of the user presets file
+    pub fn presets(&self) -> PathBuf {
+        self.config_dir.join(USER_PRESETS)
+    }
+
+    /// Creates the directories if they do not exist. On unix only the user may read them.
+    /// ### Side-effect
+    /// Creates directories on the file system
+    pub fn create(&self) -> Result<()> {
+        for dir in [&self.data_dir, &self.config_dir] {
+            let mut builder = std::fs::DirBuilder::new();
+            builder.recursive(true);
+            #[cfg(unix)]
+            std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+            builder.create(dir)
+                .map_err(|err| Error::Io(format!("cannot create {}: {}", dir.display(), err)))?;
+        }
+        Ok(())
+    }
+
+    /// Moves the databases left in a directory by builds that kept them in the working
+    /// directory, with the files sqlite keeps next to them. Only runs once per data directory,
+    /// and only moves files holding the tables of the password manager. Databases that
+    /// already exist in their new place are left where they are.
+    /// ### Params
+    /// - `old_dir`: the directory the files were kept in, usually the working directory
+    /// ### Returns
+    /// The new paths of the moved databases and the old paths of the databases left behind
+    /// ### Side-effect
+    /// Moves files on the file system, marks the data directory as checked
+    pub fn move_legacy_files(&self, old_dir: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+        let mut moved = Vec::new();
+        let mut left = Vec::new();
+        let marker = self.data_dir.join(LEGACY_MARKER);
+        if marker.exists() {
+            return Ok((moved, left));
+        }
+        for (name, table, new_path) in [(AUTH_DB, "auth", self.auth_db()), (SERVICES_DB, "services", self.services_db())] {
+            let old_path = old_dir.join(name);
+            if !old_path.is_file() || same_file(&old_path, &new_path) || !has_table(&old_path, table) {
+                continue;
+            }
+            if new_path.exists() {
+                left.push(old_path);
+                continue;
+            }
+            for suffix in SQLITE_SIDE_FILES {
+                let (old_side, new_side) = (with_suffix(&old_path, suffix), with_suffix(&new_path, suffix));
+                if old_side.is_file() {
+                    move_file(&old_side, &new_side)
+                        .map_err(|err| Error::Io(format!("cannot move {} to {}: {}", old_side.display(), new_side.display(), err)))?;
+                }
+            }
+            move_file(&old_path, &new_path)
+                .map_err(|err| Error::Io(format!("cannot move {} to {}: {}", old_path.display(), new_path.display(), err)))?;
+            moved.push(new_path);
+        }
+        std::fs::write(&marker, "")
+            .map_err(|err| Error::Io(format!("cannot write {}: {}", marker.display(), err)))?;
+        Ok((moved, left))
+    }
+}
+
+/// Checks whether two paths lead to the same existing file
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false
+    }
+}
+
+/// Checks whether a file is a sqlite database with the table, without changing it
+fn has_table(path: &Path, table: &str) -> bool {
+    let Ok(conn) = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) else {
+        return false;
+    };
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1", [table], |row| row.get::<_, u32>(0))
+        .is_ok_and(|count| count > 0)
+}
+
+/// The path with a suffix added to its file name, e.g. `auth.db-wal`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Moves a file, copying it if it is on another file system
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to)?;
+    std::fs::remove_file(from)
+}
@@ -0,0 +1,71 @@
+//! Numeric PINs for phone unlock codes and bank cards. PINs that are easy
+//! to guess (repeating, sequential or commonly used ones) are rejected and
+//! re-derived, like passwords missing a character type are.
+
+use zeroize::Zeroize;
+
+use crate::stream::KeyStream;
+
+/// Shortest PIN the generator agrees to make
+pub const MIN_PIN_LEN: u8 = 4;
+
+/// PIN length used when switching to PINs
+pub const DEFAULT_PIN_LEN: u8 = 4;
+
+/// Frequently chosen PINs that are not caught by the pattern checks
+const COMMON_PINS: [&str; 14] = [
+    "1004", "6969", "2580", "1397", "0852", "1342", "5683",
+    "112233", "123321", "159753", "147258", "147852", "159357", "789456"
+];
+
+/// Checks whether the PIN is made of one shorter block repeated, e.g. 1111, 1212 or 123123
+fn is_repeating(digits: &[u8]) -> bool {
+    (1..digits.len())
+        .filter(|period| digits.len().is_multiple_of(*period))
+        .any(|period| digits.iter().zip(&digits[period..]).all(|(a, b)| a == b))
+}
+
+/// Checks whether every digit is one more (or one less) than the one before,
+/// wrapping around from 9 to 0, e.g. 1234, 7890 or 4321
+fn is_sequential(digits: &[u8]) -> bool {
+    let step_up = digits.windows(2).all(|pair| pair[1] == (pair[0] + 1) % 10);
+    let step_down = digits.windows(2).all(|pair| pair[0] == (pair[1] + 1) % 10);
+    step_up || step_down
+}
+
+/// Checks whether a 4 digit PIN looks like a year from 1900 to 2099
+fn is_year(pin: &str) -> bool {
+    pin.len() == 4 && (pin.starts_with("19") || pin.starts_with("20"))
+}
+
+/// Checks whether a PIN is easy to guess
+/// ### Parameters
+/// - `pin`: string of ASCII digits
+/// ### Returns
+/// true if the PIN is repeating, sequential, a year or a commonly used PIN,
+/// false for anything that is not made of digits only
+pub fn is_weak_pin(pin: &str) -> bool {
+    if pin.is_empty() || !pin.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    let digits: Vec<u8> = pin.bytes().map(|b| b.wrapping_sub(b'0')).collect();
+    is_repeating(&digits) || is_sequential(&digits) || is_year(pin) || COMMON_PINS.contains(&pin)
+}
+
+/// Draws PINs from the stream until one is not weak
+/// ### Parameters
+/// - `stream`: stream seeded from the master password and salt
+/// - `length`: number of digits
+/// ### Returns
+/// The PIN
+pub(crate) fn pick(stream: &mut KeyStream, length: u8) -> String {
+    loop {
+        let mut candidate: String = (0..length)
+            .map(|_| char::from(b'0' + stream.next_index(10) as u8))
+            .collect();
+        if !is_weak_pin(&candidate) {
+            return candidate;
+        }
+        candidate.zeroize();
+    }
+}
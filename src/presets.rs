@@ -0,0 +1,154 @@
+//! Password policies of websites, so the recipe of a new service is filled in
+//! with the length and characters the site accepts. A set of presets is bundled,
+//! more can be added (or bundled ones replaced) in a user file of the same format:
+//!
+//! ```toml
+//! [[preset]]
+//! names = ["example", "example.com"]
+//! min_length = 8
+//! max_length = 20
+//! classes = "ulns"
+//! symbols = "!#$%"
+//! rules = "n>=1,repeat<=3"
+//! ```
+//!
+//! Every field but `names` is optional. `exclude` and `no_lookalikes` work like
+//! in `Charset`.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::characters::Key;
+use crate::error::{Error, Result};
+use crate::generator::{Mode, PasswordSpec};
+use crate::rules::Rules;
+
+/// Presets shipped with the password manager
+const BUNDLED_PRESETS: &str = include_str!("presets/sites.toml");
+
+/// Name of the user presets file in the configuration directory
+pub const USER_PRESETS: &str = "presets.toml";
+
+/// What a website accepts as a password
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    /// Service titles and domains the preset is used for
+    pub names: Vec<String>,
+    pub min_length: Option<u8>,
+    pub max_length: Option<u8>,
+    /// Character types as in `Key::from_classes()`
+    pub classes: Option<String>,
+    /// Symbols the site accepts
+    pub symbols: Option<String>,
+    /// Characters the site rejects
+    pub exclude: Option<String>,
+    pub no_lookalikes: Option<bool>,
+    /// Rules as in `Rules::parse()`
+    pub rules: Option<String>
+}
+
+impl Preset {
+    /// Fills in a password spec with the preset. The length is kept if the site
+    /// accepts it and moved into the accepted range otherwise.
+    /// ### Returns
+    /// `Error::InvalidSpec` if the preset has invalid values or cannot be generated with the spec
+    pub fn apply(&self, spec: &mut PasswordSpec) -> Result<()> {
+        spec.set_mode(Mode::Password);
+        if let Some(max_length) = self.max_length {
+            spec.length = spec.length.min(max_length);
+        }
+        if let Some(min_length) = self.min_length {
+            spec.length = spec.length.max(min_length);
+        }
+        if let Some(classes) = &self.classes {
+            spec.key = Key::from_classes(classes)?;
+        }
+        if let Some(symbols) = &self.symbols {
+            spec.charset.set_symbols(symbols)?;
+        }
+        if let Some(exclude) = &self.exclude {
+            spec.charset.set_excluded(exclude)?;
+        }
+        if let Some(no_lookalikes) = self.no_lookalikes {
+            spec.charset.no_lookalikes = no_lookalikes;
+        }
+        if let Some(rules) = &self.rules {
+            spec.rules = Rules::parse(rules)?;
+        }
+        spec.validate()
+    }
+}
+
+/// The layout of a presets file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PresetFile {
+    #[serde(default)]
+    preset: Vec<Preset>
+}
+
+/// Parses the contents of a presets file, `source` names the file in errors
+fn parse(contents: &str, source: &str) -> Result<Vec<Preset>> {
+    toml::from_str::<PresetFile>(contents)
+        .map(|file| file.preset)
+        .map_err(|err| Error::Preset(format!("{}: {}", source, err)))
+}
+
+/// Makes service titles and domains comparable, e.g. `https://www.Example.com/login` becomes `example.com`
+/// ### Returns
+/// The lower cased title without URL scheme, `www.` and path
+pub fn normalize(title: &str) -> String {
+    let title = title.trim().to_lowercase();
+    let title = title.split_once("://").map(|(_, rest)| rest).unwrap_or(&title);
+    let title = title.strip_prefix("www.").unwrap_or(title);
+    title.split('/').next().unwrap_or_default().to_string()
+}
+
+/// The bundled presets together with the ones of the user
+#[derive(Clone, Debug, Default)]
+pub struct Presets {
+    /// User presets first so they are found before bundled ones
+    presets: Vec<Preset>
+}
+
+impl Presets {
+    /// Only the bundled presets
+    pub fn bundled() -> Self {
+        Presets { presets: parse(BUNDLED_PRESETS, "bundled presets").expect("bundled presets are valid") }
+    }
+
+    /// The bundled presets and the ones in the user file. A missing user file is not an error.
+    /// ### Params
+    /// - `filename`: path of the user presets file, usually `Paths::presets()`
+    /// ### Returns
+    /// The presets or `Error::Preset` if the user file cannot be read or parsed
+    /// ### Side-effect
+    /// Reads the user presets file
+    pub fn load(filename: impl AsRef<Path>) -> Result<Self> {
+        let filename = filename.as_ref();
+        let mut presets = match std::fs::read_to_string(filename) {
+            Ok(contents) => parse(&contents, &filename.display().to_string())?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(Error::Preset(format!("{}: {}", filename.display(), err)))
+        };
+        presets.extend(Presets::bundled().presets);
+        Ok(Presets { presets })
+    }
+
+    /// Looks up the preset of a service
+    /// ### Params
+    /// - `title`: service title or domain, compared after `normalize()`
+    /// ### Returns
+    /// The first preset listing the title among its names
+    pub fn find(&self, title: &str) -> Option<&Preset> {
+        let title = normalize(title);
+        self.presets.iter().find(|preset| preset.names.iter().any(|name| normalize(name) == title))
+    }
+
+    /// Every preset, user presets first
+    pub fn iter(&self) -> impl Iterator<Item = &Preset> {
+        self.presets.iter()
+    }
+}
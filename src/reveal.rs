@@ -0,0 +1,52 @@
+//! The n_curses overlay that shows a generated password on screen, as text or as
+//! a QR code, and wipes it once a key is pressed
+
+extern crate ncurses;
+
+use ncurses::*;
+use zeroize::Zeroize;
+
+/// Shows the lines centered on the cleared screen until a key is pressed, then wipes the screen.
+/// The screen is redrawn when the terminal is resized.
+/// ### Params
+/// - `title`: first line, e.g. the name of the service
+/// - `lines`: what to show, wiped from memory afterwards
+/// ### Side-effect
+/// Draws over the active n_curses screen until a key is pressed
+pub fn show(title: &str, mut lines: Vec<String>) {
+    let footer = "Press any key to hide";
+    let width = lines.iter().map(|line| line.chars().count()).chain([title.chars().count(), footer.len()]).max().unwrap_or(0) as i32;
+    let height = lines.len() as i32 + 4;
+
+    timeout(-1);
+    loop {
+        let (max_y, max_x) = (LINES(), COLS());
+        erase();
+        if width > max_x || height > max_y {
+            let message = format!("Make the terminal at least {}x{} to show this, it is {}x{}.", width, height, max_x, max_y);
+            mvaddstr(0, 0, &message);
+            mvaddstr(1, 0, footer);
+        } else {
+            let top = (max_y - height) / 2;
+            let left = (max_x - width) / 2;
+            attron(A_BOLD());
+            mvaddstr(top, left, title);
+            attroff(A_BOLD());
+            for (row, line) in lines.iter().enumerate() {
+                mvaddstr(top + 2 + row as i32, left, line);
+            }
+            mvaddstr(top + height - 1, left, footer);
+        }
+        refresh();
+        if getch() != KEY_RESIZE {
+            break;
+        }
+    }
+
+    // Overwrite the password on screen before leaving, so nothing is left in the terminal
+    erase();
+    refresh();
+    for line in lines.iter_mut() {
+        line.zeroize();
+    }
+}
@@ -0,0 +1,188 @@
+//! Password policies beyond the character types, e.g. "at least 2 digits and
+//! 2 symbols" or "no more than 2 repeated characters". Passwords breaking a
+//! rule are rejected and re-drawn, like passwords missing a character type are.
+//! Rules that almost no password follows are given up on with an error.
+//!
+//! Rules are written as a comma separated list:
+//! - `n>=2`: at least 2 characters of a type (`u`, `l`, `n` or `s` like in `Key::from_classes()`)
+//! - `s<=4`: at most 4 characters of a type
+//! - `repeat<=2`: no character more than 2 times in a row
+//! - `letter-first`: the password starts with a letter
+
+use std::fmt;
+
+use crate::error::{Error, Result};
+
+/// Letters of the character types in the order rules are written in
+const CLASSES: [char; 4] = ['u', 'l', 'n', 's'];
+
+/// Names of the character types for error messages
+const CLASS_NAMES: [&str; 4] = ["uppercase", "lowercase", "number", "symbol"];
+
+/// Index of the character type of a password character in `CLASSES`
+fn class_of(c: char) -> usize {
+    if c.is_ascii_uppercase() {
+        0
+    } else if c.is_ascii_lowercase() {
+        1
+    } else if c.is_ascii_digit() {
+        2
+    } else {
+        3
+    }
+}
+
+/// Rules a password has to follow. The default has no rules.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rules {
+    /// Fewest characters of every type, for uppercase, lowercase, numbers and symbols respectively
+    pub min: [u8; 4],
+    /// Most characters of every type, `None` for no limit
+    pub max: [Option<u8>; 4],
+    /// Most times a character may appear in a row, `None` for no limit
+    pub max_repeat: Option<u8>,
+    /// The password starts with a letter
+    pub letter_first: bool
+}
+
+impl Rules {
+    /// Parses rules written as described in the module documentation. Empty means no rules.
+    /// ### Returns
+    /// The rules or `Error::InvalidSpec` for anything that is not a rule
+    pub fn parse(rules: &str) -> Result<Self> {
+        let mut parsed = Rules::default();
+        for rule in rules.split(',').map(str::trim).filter(|rule| !rule.is_empty()) {
+            let invalid = || Error::InvalidSpec(format!("unknown rule '{}'", rule));
+            if rule == "letter-first" {
+                parsed.letter_first = true;
+                continue;
+            }
+            let (name, count, is_min) = match (rule.split_once(">="), rule.split_once("<=")) {
+                (Some((name, count)), None) => (name, count, true),
+                (None, Some((name, count))) => (name, count, false),
+                _ => return Err(invalid())
+            };
+            let count: u8 = count.trim().parse().map_err(|_| invalid())?;
+            match (name.trim(), is_min) {
+                ("repeat", false) => parsed.max_repeat = Some(count),
+                (class, _) => {
+                    let index = CLASSES.iter()
+                        .position(|c| class.len() == 1 && class.starts_with(*c))
+                        .ok_or_else(invalid)?;
+                    if is_min {
+                        parsed.min[index] = count;
+                    } else {
+                        parsed.max[index] = Some(count);
+                    }
+                }
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Checks that a password of the given length can follow every rule
+    /// ### Parameters
+    /// - `length`   : number of characters of the password
+    /// - `available`: number of characters left of uppercase, lowercase, numbers and symbols,
+    ///   0 for types that are not enabled
+    /// ### Returns
+    /// `Err(Error::InvalidSpec)` describing the first rule that cannot be followed
+    pub(crate) fn validate(&self, length: u8, available: [usize; 4]) -> Result<()> {
+        let enabled = available.map(|chars| chars > 0);
+        let alphabet_len: usize = available.iter().sum();
+        let mut min_total = 0u32;
+        let mut max_total = 0u32;
+        for index in 0..CLASSES.len() {
+            let min = self.min[index].max(enabled[index] as u8);
+            if !enabled[index] && self.min[index] > 0 {
+                return Err(Error::InvalidSpec(format!("rules need {} characters but they are not enabled", CLASS_NAMES[index])));
+            }
+            if self.max[index].is_some_and(|max| max < min) {
+                return Err(Error::InvalidSpec(format!("rules allow fewer {} characters than they need", CLASS_NAMES[index])));
+            }
+            min_total += min as u32;
+            if enabled[index] {
+                max_total += self.max[index].unwrap_or(length) as u32;
+            }
+        }
+        if min_total > length as u32 {
+            return Err(Error::InvalidSpec(format!("rules need more than {} characters", length)));
+        }
+        if max_total < length as u32 {
+            return Err(Error::InvalidSpec(format!("rules allow fewer than {} characters", length)));
+        }
+        if self.max_repeat == Some(0) || (alphabet_len == 1 && self.max_repeat.is_some_and(|max| max < length)) {
+            return Err(Error::InvalidSpec("rules do not allow enough repeated characters".to_string()));
+        }
+        // a type with a single character left can only have that many of it in a row,
+        // with characters of other types in between
+        if let Some(max_repeat) = self.max_repeat {
+            for index in 0..CLASSES.len() {
+                let others: u32 = (0..CLASSES.len())
+                    .filter(|other| *other != index && enabled[*other])
+                    .map(|other| self.max[other].unwrap_or(length) as u32)
+                    .sum();
+                let needed = (self.min[index] as u32).max((length as u32).saturating_sub(others));
+                let separators = length as u32 - needed;
+                if available[index] == 1 && needed > max_repeat as u32 * (separators + 1) {
+                    return Err(Error::InvalidSpec(format!(
+                        "rules need more {} characters than one character repeated at most {} times in a row allows",
+                        CLASS_NAMES[index], max_repeat)));
+                }
+            }
+        }
+        if self.letter_first && !(enabled[0] || enabled[1]) {
+            return Err(Error::InvalidSpec("rules need a letter first but letters are not enabled".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Checks whether the password follows every rule
+    pub fn check(&self, pass: &str) -> bool {
+        let mut counts = [0u8; 4];
+        for c in pass.chars() {
+            counts[class_of(c)] = counts[class_of(c)].saturating_add(1);
+        }
+        for (index, count) in counts.into_iter().enumerate() {
+            if count < self.min[index] || self.max[index].is_some_and(|max| count > max) {
+                return false;
+            }
+        }
+
+        if let Some(max_repeat) = self.max_repeat {
+            let mut run = 0;
+            let mut previous = None;
+            for c in pass.chars() {
+                run = if previous == Some(c) { run + 1 } else { 1 };
+                previous = Some(c);
+                if run > max_repeat {
+                    return false;
+                }
+            }
+        }
+
+        !self.letter_first || pass.starts_with(|c: char| c.is_ascii_alphabetic())
+    }
+}
+
+impl fmt::Display for Rules {
+    /// Writes the rules in the form `parse()` reads, empty for no rules
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rules = Vec::new();
+        for (index, class) in CLASSES.iter().enumerate() {
+            if self.min[index] > 0 {
+                rules.push(format!("{}>={}", class, self.min[index]));
+            }
+            if let Some(max) = self.max[index] {
+                rules.push(format!("{}<={}", class, max));
+            }
+        }
+        if let Some(max_repeat) = self.max_repeat {
+            rules.push(format!("repeat<={}", max_repeat));
+        }
+        if self.letter_first {
+            rules.push("letter-first".to_string());
+        }
+        write!(f, "{}", rules.join(","))
+    }
+}
@@ -0,0 +1,67 @@
+//! Versioned schemas of the sqlite databases. The schema version of a database is
+//! kept in `PRAGMA user_version`. Opening a database applies the migrations it is
+//! missing in order, each in its own transaction, and refuses databases written by
+//! a newer build so their data is not misread or overwritten.
+//!
+//! New columns or tables are added by appending a migration to the list of the
+//! database, migrations already released must never change.
+
+use std::path::Path;
+
+use rusqlite::Connection;
+
+use crate::error::{Error, Result};
+
+/// Brings a database from the previous schema version to the next
+pub(crate) type Migration = fn(&Connection) -> rusqlite::Result<()>;
+
+/// Opens a database and migrates it to the newest schema version
+/// ### Params
+/// - `filename`: path of the database file, created if it does not exist
+/// - `migrations`: every migration of the database, the schema version is the number applied
+/// ### Returns
+/// The connection or `Error::Database` if the database cannot be opened or migrated,
+/// or has a newer schema version than `migrations` know about
+/// ### Side-effect
+/// Writes the missing migrations to the database
+pub(crate) fn open(filename: &Path, migrations: &[Migration]) -> Result<Connection> {
+    let mut conn = Connection::open(filename)?;
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > migrations.len() {
+        return Err(Error::Database(format!(
+            "{} has schema version {} but this build only knows up to {}, it was written by a newer build",
+            filename.display(), version, migrations.len())));
+    }
+
+    for (applied, migration) in migrations.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", applied + 1)?;
+        tx.commit()?;
+    }
+    Ok(conn)
+}
+
+/// Reads the schema version of an open database
+pub fn version(conn: &Connection) -> Result<usize> {
+    Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+/// Checks whether a table has a column
+pub(crate) fn has_column(conn: &Connection, table: &str, column: &str) -> bool {
+    conn.prepare(format!("SELECT {} FROM {}", column, table).as_str()).is_ok()
+}
+
+/// Adds the columns a table made by a build without schema versions may be missing
+/// ### Params
+/// - `table`: name of the table
+/// - `columns`: column definitions, starting with the column name
+pub(crate) fn add_missing_columns(conn: &Connection, table: &str, columns: &[&str]) -> rusqlite::Result<()> {
+    for column in columns {
+        let name = column.split_whitespace().next().unwrap_or_default();
+        if !has_column(conn, table, name) {
+            conn.execute(format!("ALTER TABLE {} ADD COLUMN {}", table, column).as_str(), ())?;
+        }
+    }
+    Ok(())
+}
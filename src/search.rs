@@ -0,0 +1,119 @@
+//! Fuzzy search over service titles. Like fzf the characters of the query have to
+//! appear in the title in order but not next to each other, so `gthb` finds `github`.
+//! Matches are ranked by how well they match, then by how recently and how often the
+//! service was used.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How often and when a password was last generated for a service
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// Unix time in seconds of the last use, None if never used
+    pub last_used: Option<i64>,
+    /// Number of uses
+    pub count: u32
+}
+
+/// Score of every matched character
+const SCORE_MATCH: i64 = 16;
+/// Bonus for matching the first character of a word, e.g. `b` in `my-bank`
+const BONUS_BOUNDARY: i64 = 8;
+/// The bonus of the first character of the query counts this many times, what it starts at matters most
+const FIRST_CHAR_MULTIPLIER: i64 = 2;
+/// Bonus for matching right after the previous matched character
+const BONUS_CONSECUTIVE: i64 = 12;
+/// Penalty for skipping characters between two matched characters
+const PENALTY_GAP_START: i64 = 3;
+/// Penalty for every further skipped character
+const PENALTY_GAP_EXTENSION: i64 = 1;
+/// Largest bonus for frequent and for recent use each, kept below a few matched characters
+/// so usage only decides between similar matches
+const MAX_USAGE_BONUS: i64 = 24;
+
+/// Scores how well the query matches the text, ignoring case
+/// ### Params
+/// - `query`: what was typed, taken as is without any special characters
+/// - `text`: the title to match
+/// ### Returns
+/// None if the characters of the query are not in the text in order, otherwise the
+/// score of the best way to match them, higher is better. Empty queries match with 0.
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().map(lowercase).collect();
+    let text: Vec<char> = text.chars().collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let bonus: Vec<i64> = (0..text.len())
+        .map(|j| if j == 0 || !text[j - 1].is_alphanumeric() { BONUS_BOUNDARY } else { 0 })
+        .collect();
+    let text: Vec<char> = text.into_iter().map(lowercase).collect();
+
+    // best[j]: best score of the query so far with its last character matched at text[j]
+    let mut best: Vec<Option<i64>> = text.iter().enumerate()
+        .map(|(j, c)| (*c == query[0]).then(|| SCORE_MATCH + FIRST_CHAR_MULTIPLIER * bonus[j]))
+        .collect();
+    for q in &query[1..] {
+        let mut next = vec![None; text.len()];
+        for (j, c) in text.iter().enumerate() {
+            if c != q {
+                continue;
+            }
+            next[j] = (0..j)
+                .filter_map(|k| best[k].map(|score| {
+                    let gap = (j - k - 1) as i64;
+                    score + if gap == 0 { BONUS_CONSECUTIVE } else { -PENALTY_GAP_START - PENALTY_GAP_EXTENSION * (gap - 1) }
+                }))
+                .max()
+                .map(|score| score + SCORE_MATCH + bonus[j]);
+        }
+        best = next;
+    }
+    best.into_iter().flatten().max()
+}
+
+/// Bonus for services used often and recently
+/// ### Params
+/// - `usage`: how the service was used
+/// - `now`: the current Unix time in seconds
+pub fn usage_bonus(usage: Usage, now: i64) -> i64 {
+    let frequency = (i64::from((usage.count.saturating_add(1)).ilog2()) * 4).min(MAX_USAGE_BONUS);
+    // halves after a week, mostly gone after a few months
+    let recency = usage.last_used.map_or(0, |last_used| {
+        let days = (now - last_used).max(0) / (24 * 60 * 60);
+        MAX_USAGE_BONUS * 7 / (7 + days)
+    });
+    frequency + recency
+}
+
+/// Keeps the candidates matching the query, best first
+/// ### Params
+/// - `query`: what was typed
+/// - `candidates`: what to rank with its title and usage
+/// - `now`: the current Unix time in seconds
+/// ### Returns
+/// The matching candidates, a title equal to the query first, then ordered by match score
+/// plus usage bonus, then shorter titles, then by title
+pub fn rank<'a, T>(query: &str, candidates: impl IntoIterator<Item = (T, &'a str, Usage)>, now: i64) -> Vec<T> {
+    let query_lowercase: String = query.chars().map(lowercase).collect();
+    let mut matches: Vec<(bool, i64, &str, T)> = candidates.into_iter()
+        .filter_map(|(candidate, title, usage)| score(query, title).map(|score| {
+            let exact = title.chars().map(lowercase).eq(query_lowercase.chars());
+            (exact, score + usage_bonus(usage, now), title, candidate)
+        }))
+        .collect();
+    matches.sort_by(|a, b| b.0.cmp(&a.0)
+        .then(b.1.cmp(&a.1))
+        .then(a.2.chars().count().cmp(&b.2.chars().count()))
+        .then(a.2.cmp(b.2)));
+    matches.into_iter().map(|(_, _, _, candidate)| candidate).collect()
+}
+
+/// The current Unix time in seconds, as `rank()` and `service_db_actions::record_use()` take it
+pub fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs() as i64)
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
@@ -1,102 +1,677 @@
-extern crate rusqlite;
+//! Reads and writes the service records used for auto-completion and
+//! remembering the recipe (password number, length, character types and
+//! set, rules, generation algorithm and mode) of every service. Every user
+//! has their own services. How often and when every service was used is
+//! kept for ranking search results. Services can be renamed without changing
+//! their password, deleted, merged and given aliases sharing their recipe.
+//!
+//! Titles are shown as they were typed but compared ignoring case. The password
+//! of a service is derived from its derivation id instead of its title, which
+//! never changes once the service is saved.
 
-use rusqlite::{Connection, Result};
-use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
-/// Creates the database table for service titles if they do not exist
+use rusqlite::{params, Connection, Result, Row};
+
+use crate::characters::Key;
+use crate::error::Error;
+use crate::generator::{Algorithm, Mode, PasswordSpec};
+use crate::passphrase::PassphraseSpec;
+use crate::rules::Rules;
+use crate::schema::{self, Migration};
+use crate::search::Usage;
+
+/// Migrations of the service records database, see `schema`
+const MIGRATIONS: [Migration; 4] = [create_tables, add_usage, add_salt_titles_and_aliases, add_derivation_ids];
+
+/// Columns of the recipe, services made before a column existed get its default
+const RECIPE_COLUMNS: [&str; 11] = [
+    "algorithm INTEGER NOT NULL DEFAULT 1",
+    "length INTEGER NOT NULL DEFAULT 16",
+    "classes TEXT NOT NULL DEFAULT 'ulns'",
+    "mode TEXT NOT NULL DEFAULT 'password'",
+    "separator TEXT NOT NULL DEFAULT ' '",
+    "capitalize INTEGER NOT NULL DEFAULT 0",
+    "digit INTEGER NOT NULL DEFAULT 0",
+    "symbols TEXT",
+    "excluded TEXT NOT NULL DEFAULT ''",
+    "no_lookalikes INTEGER NOT NULL DEFAULT 0",
+    "rules TEXT NOT NULL DEFAULT ''"
+];
+
+/// Columns of a service from schema version 1 to 3, copied by the rebuild of schema version 4
+const V1_RECORD_COLUMNS: &str = "title, pass_num, algorithm, length, classes, mode, separator, capitalize, digit, \
+                                 symbols, excluded, no_lookalikes, rules";
+
+/// Schema version 1: the services of every user with their recipes
 /// ### Parameters
 /// - `conn` : Connection to a sqlite database (rusqlite object)
 /// ### Side-effects
-/// Creates tables if they do not exist. Quietly fails to do so if they already
-/// exists
-fn create_tables(conn: &Connection) {
-    let query = "CREATE TABLE services(
-        title TEXT NOT NULL UNIQUE,
-        pass_num INTEGER
-    )";
-
-    match conn.execute(query, ()) {
-        Ok(_) => (),
-        Err(_) => ()
+/// Creates the table. Databases made before schema versions may already have it:
+/// their services get the recipe columns they are missing, so they are pinned
+/// to algorithm version 1 which generated their passwords and get the default
+/// length and character types. A missing list of symbols allows every symbol.
+/// Tables made before services belonged to users are rebuilt keyed by user,
+/// their services belong to no user until assigned with `assign_unowned()`.
+fn create_tables(conn: &Connection) -> Result<()> {
+    // written out so later changes to the recipe columns cannot change this migration
+    conn.execute("CREATE TABLE IF NOT EXISTS services(
+        username TEXT,
+        title TEXT NOT NULL,
+        pass_num INTEGER,
+        algorithm INTEGER NOT NULL DEFAULT 1,
+        length INTEGER NOT NULL DEFAULT 16,
+        classes TEXT NOT NULL DEFAULT 'ulns',
+        mode TEXT NOT NULL DEFAULT 'password',
+        separator TEXT NOT NULL DEFAULT ' ',
+        capitalize INTEGER NOT NULL DEFAULT 0,
+        digit INTEGER NOT NULL DEFAULT 0,
+        symbols TEXT,
+        excluded TEXT NOT NULL DEFAULT '',
+        no_lookalikes INTEGER NOT NULL DEFAULT 0,
+        rules TEXT NOT NULL DEFAULT '',
+        UNIQUE(username, title)
+    )", ())?;
+    schema::add_missing_columns(conn, "services", &RECIPE_COLUMNS)?;
+
+    // titles were unique across users before, the table has to be rebuilt to change that
+    if !schema::has_column(conn, "services", "username") {
+        conn.execute_batch("CREATE TABLE services_by_user(
+                username TEXT,
+                title TEXT NOT NULL,
+                pass_num INTEGER,
+                algorithm INTEGER NOT NULL DEFAULT 1,
+                length INTEGER NOT NULL DEFAULT 16,
+                classes TEXT NOT NULL DEFAULT 'ulns',
+                mode TEXT NOT NULL DEFAULT 'password',
+                separator TEXT NOT NULL DEFAULT ' ',
+                capitalize INTEGER NOT NULL DEFAULT 0,
+                digit INTEGER NOT NULL DEFAULT 0,
+                symbols TEXT,
+                excluded TEXT NOT NULL DEFAULT '',
+                no_lookalikes INTEGER NOT NULL DEFAULT 0,
+                rules TEXT NOT NULL DEFAULT '',
+                UNIQUE(username, title)
+            );
+            INSERT INTO services_by_user (title, pass_num, algorithm, length, classes, mode, separator, capitalize, digit,
+                                          symbols, excluded, no_lookalikes, rules)
+                SELECT title, pass_num, algorithm, length, classes, mode, separator, capitalize, digit,
+                       symbols, excluded, no_lookalikes, rules FROM services;
+            DROP TABLE services;
+            ALTER TABLE services_by_user RENAME TO services;")?;
+    }
+    Ok(())
+}
+
+/// Schema version 2: when and how often every service was used, for ranking search results.
+/// Services used before are taken as never used.
+fn add_usage(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE services ADD COLUMN last_used INTEGER;
+        ALTER TABLE services ADD COLUMN use_count INTEGER NOT NULL DEFAULT 0;")
+}
+
+/// Schema version 3: the title in the salt of every service, kept when the service is
+/// renamed so its password does not change, and other names of services
+fn add_salt_titles_and_aliases(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE services ADD COLUMN salt_title TEXT;
+        UPDATE services SET salt_title = title;
+        CREATE TABLE aliases(
+            username TEXT NOT NULL,
+            alias TEXT NOT NULL,
+            title TEXT NOT NULL,
+            UNIQUE(username, alias)
+        );")
+}
+
+/// Schema version 4: titles and aliases compared ignoring case so they can keep the case
+/// they were typed in, the salt title becomes the derivation id, and notes and tags.
+/// The tables are rebuilt since the collation of a column cannot be changed.
+/// Titles and aliases differing only in case were allowed before, see `rename_case_duplicates()`.
+fn add_derivation_ids(conn: &Connection) -> Result<()> {
+    for (username, title, renamed) in rename_case_duplicates(conn, "services", "title")? {
+        conn.execute("UPDATE aliases SET title = ?3 WHERE username IS ?1 AND title = ?2", params![username, title, renamed])?;
+    }
+    rename_case_duplicates(conn, "aliases", "alias")?;
+    number_duplicate_derivation_ids(conn)?;
+
+    let query = format!("CREATE TABLE services_v4(
+            username TEXT,
+            title TEXT NOT NULL COLLATE NOCASE,
+            derivation_id TEXT NOT NULL,
+            pass_num INTEGER,
+            {},
+            last_used INTEGER,
+            use_count INTEGER NOT NULL DEFAULT 0,
+            notes TEXT NOT NULL DEFAULT '',
+            tags TEXT NOT NULL DEFAULT '',
+            UNIQUE(username, title),
+            UNIQUE(username, derivation_id)
+        );
+        INSERT INTO services_v4 (username, {record_columns}, derivation_id, last_used, use_count)
+            SELECT username, {record_columns}, COALESCE(salt_title, title), last_used, use_count FROM services;
+        DROP TABLE services;
+        ALTER TABLE services_v4 RENAME TO services;
+        CREATE TABLE aliases_v4(
+            username TEXT NOT NULL,
+            alias TEXT NOT NULL COLLATE NOCASE,
+            title TEXT NOT NULL COLLATE NOCASE,
+            UNIQUE(username, alias)
+        );
+        INSERT INTO aliases_v4 (username, alias, title) SELECT username, alias, title FROM aliases;
+        DROP TABLE aliases;
+        ALTER TABLE aliases_v4 RENAME TO aliases;", RECIPE_COLUMNS.join(",\n"), record_columns = V1_RECORD_COLUMNS);
+    conn.execute_batch(query.as_str())
+}
+
+/// Renames names of a user that only differ in case from an earlier name by adding a
+/// number, e.g. `Gmail` next to `gmail` becomes `Gmail (2)`. Nothing is merged, so
+/// renamed services keep their own password.
+/// ### Params
+/// - `table`: the table of the names, `services` or `aliases`
+/// - `column`: the column of the names in the table
+/// ### Returns
+/// The user, old and new name of every renamed row
+fn rename_case_duplicates(conn: &Connection, table: &str, column: &str) -> Result<Vec<(Option<String>, String, String)>> {
+    let rows: Vec<(i64, Option<String>, String)> = conn.prepare(&format!("SELECT rowid, username, {} FROM {} ORDER BY rowid", column, table))?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<_>>()?;
+    let mut taken: HashSet<(Option<String>, String)> = rows.iter()
+        .map(|(_, username, name)| (username.clone(), name.to_ascii_lowercase()))
+        .collect();
+    let mut seen = HashSet::new();
+    let mut renamed = Vec::new();
+    for (rowid, username, name) in rows {
+        if seen.insert((username.clone(), name.to_ascii_lowercase())) {
+            continue;
+        }
+        let new_name = (2..).map(|number| format!("{} ({})", name, number))
+            .find(|candidate| !taken.contains(&(username.clone(), candidate.to_ascii_lowercase())))
+            .unwrap_or_default();
+        taken.insert((username.clone(), new_name.to_ascii_lowercase()));
+        seen.insert((username.clone(), new_name.to_ascii_lowercase()));
+        conn.execute(&format!("UPDATE {} SET {} = ?2 WHERE rowid = ?1", table, column), params![rowid, new_name])?;
+        renamed.push((username, name, new_name));
+    }
+    Ok(renamed)
+}
+
+/// Numbers the salt titles a user has more than once, e.g. after renaming `gmial` to `gmail`
+/// and adding `gmial` again, so every service gets its own password. The first service
+/// keeps its salt title, the password of the later ones changes.
+fn number_duplicate_derivation_ids(conn: &Connection) -> Result<()> {
+    let rows: Vec<(i64, Option<String>, String)> = conn.prepare("SELECT rowid, username, COALESCE(salt_title, title) FROM services ORDER BY rowid")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<_>>()?;
+    let mut taken: HashSet<(Option<String>, String)> = rows.iter()
+        .map(|(_, username, id)| (username.clone(), id.clone()))
+        .collect();
+    let mut seen = HashSet::new();
+    for (rowid, username, id) in rows {
+        // services saved before they belonged to users never collide
+        if username.is_none() || seen.insert((username.clone(), id.clone())) {
+            continue;
+        }
+        let new_id = (2..).map(|number| numbered_derivation_id(&id, number))
+            .find(|candidate| !taken.contains(&(username.clone(), candidate.clone())))
+            .unwrap_or_default();
+        taken.insert((username.clone(), new_id.clone()));
+        conn.execute("UPDATE services SET salt_title = ?2 WHERE rowid = ?1", params![rowid, new_id])?;
+    }
+    Ok(())
+}
+
+/// The derivation id used when `id` is taken by another service of the user
+fn numbered_derivation_id(id: &str, number: u32) -> String {
+    format!("{}#{}", id, number)
+}
+
+/// Columns making up a `ServiceRecord`, in the order `record_from_row` reads them
+const RECORD_COLUMNS: &str = "title, pass_num, algorithm, length, classes, mode, separator, capitalize, digit, \
+                              symbols, excluded, no_lookalikes, rules, derivation_id, notes, tags";
+
+/// Columns `update_db()` never changes once a service is saved
+const FIXED_COLUMNS: [&str; 2] = ["title", "derivation_id"];
+
+/// A remembered service with the recipe its password was last generated with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceRecord {
+    /// The name shown for the service, can be changed
+    pub title: String,
+    /// Identifies the service in the salt of its password, never changes once saved
+    pub derivation_id: String,
+    pub pass_num: u8,
+    pub spec: PasswordSpec,
+    pub notes: String,
+    pub tags: Vec<String>
+}
+
+impl ServiceRecord {
+    /// Makes the record of a service that has not been used before. New services
+    /// get the default length and character types and the newest algorithm.
+    /// Their derivation id is the title in lowercase, so a title gives the same
+    /// password on every machine and in every case it is typed in. Use
+    /// `unused_derivation_id()` before saving it next to other services.
+    pub fn new(title: &str, pass_num: u8) -> Self {
+        ServiceRecord {
+            title: title.to_string(),
+            derivation_id: title.to_ascii_lowercase(),
+            pass_num,
+            spec: PasswordSpec { algorithm: Algorithm::LATEST, ..PasswordSpec::default() },
+            notes: String::new(),
+            tags: Vec::new()
+        }
+    }
+
+    /// The part of the salt that identifies the service
+    /// ### Returns
+    /// The derivation id followed by the password number
+    pub fn salt_part(&self) -> String {
+        format!("{}{}", self.derivation_id, self.pass_num)
+    }
+}
+
+/// The derivation id for a new service of the title: the title in lowercase, numbered if
+/// another service of the user already has it, e.g. after renaming `gmial` to `gmail`
+/// and adding `gmial` again
+/// ### Side-effect
+/// Reads from database
+pub fn unused_derivation_id(conn: &Connection, username: &str, title: &str) -> Result<String> {
+    let taken: HashSet<String> = conn.prepare("SELECT derivation_id FROM services WHERE username = ?1")?
+        .query_map(params![username], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+    let id = title.to_ascii_lowercase();
+    if !taken.contains(&id) {
+        return Ok(id);
+    }
+    Ok((2..).map(|number| numbered_derivation_id(&id, number))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap_or_default())
+}
+
+/// Splits tags separated by commas, leaving out empty and repeated ones
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    let mut parsed: Vec<String> = Vec::new();
+    for tag in tags.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !parsed.iter().any(|known| known.eq_ignore_ascii_case(tag)) {
+            parsed.push(tag.to_string());
+        }
+    }
+    parsed
+}
+
+/// Makes a `ServiceRecord` from a row selected as `RECORD_COLUMNS`
+fn record_from_row(row: &Row) -> Result<ServiceRecord> {
+    let number: u8 = row.get(2)?;
+    let algorithm = Algorithm::from_number(number)
+        .map_err(|_| rusqlite::Error::IntegralValueOutOfRange(2, number as i64))?;
+    let classes: String = row.get(4)?;
+    let key = Key::from_classes(&classes)
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(err)))?;
+    let mut spec = PasswordSpec::new(row.get(3)?, key);
+    spec.algorithm = algorithm;
+    let symbols: Option<String> = row.get(9)?;
+    let excluded: String = row.get(10)?;
+    if let Some(symbols) = symbols {
+        spec.charset.set_symbols(&symbols)
+            .map_err(|err| rusqlite::Error::FromSqlConversionFailure(9, rusqlite::types::Type::Text, Box::new(err)))?;
+    }
+    spec.charset.set_excluded(&excluded)
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(10, rusqlite::types::Type::Text, Box::new(err)))?;
+    spec.charset.no_lookalikes = row.get(11)?;
+    let rules: String = row.get(12)?;
+    spec.rules = Rules::parse(&rules)
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(12, rusqlite::types::Type::Text, Box::new(err)))?;
+    let mode: String = row.get(5)?;
+    spec.mode = match mode.as_str() {
+        "password" => Mode::Password,
+        "pin" => Mode::Pin,
+        "passphrase" => {
+            let separator: String = row.get(6)?;
+            Mode::Passphrase(PassphraseSpec {
+                separator: separator.chars().next(),
+                capitalize: row.get(7)?,
+                digit: row.get(8)?
+            })
+        },
+        _ => return Err(rusqlite::Error::InvalidColumnType(5, mode, rusqlite::types::Type::Text))
     };
+    let tags: String = row.get(15)?;
+    Ok(ServiceRecord {
+        title: row.get(0)?,
+        derivation_id: row.get(13)?,
+        pass_num: row.get(1)?,
+        spec,
+        notes: row.get(14)?,
+        tags: parse_tags(&tags)
+    })
 }
 
+/// Name of the service records database file in the data directory
+pub const SERVICES_DB: &str = "service_records.db";
+
 /// Creates a connection to a sqlite database file. If file does not exist,
-/// creates a file in CWD and then opens it with a connection. This function is
-/// used by the service-associated database interactions only
+/// creates the file and then opens it with a connection. The database is
+/// migrated to the newest schema version. This function is used by the
+/// service-associated database interactions only
 /// ### Params
-/// - `filename`: the name of the database file in the current working director
+/// - `filename`: path of the database file, usually `Paths::services_db()`
 /// ### Returns
-/// Rusqlite Connection to the database 
+/// Rusqlite Connection to the database, or `Error::Database` if it cannot be
+/// opened or was written by a newer build
 /// ### Side-effect
 /// Makes connection with sqlite database
-pub fn get_connection(filename: &str) -> Connection {
-    let conn = Connection::open(filename).unwrap();
-    create_tables(&conn);
-    conn
+pub fn get_connection(filename: impl AsRef<Path>) -> crate::error::Result<Connection> {
+    schema::open(filename.as_ref(), &MIGRATIONS)
 }
 
-/// Inserts new services into the database for future tab-to-complete.
-/// Also updates the password number if a new password number is used.
+/// Inserts new services into the database so they can be searched for.
+/// Also updates the password number, recipe, notes and tags if new ones are used.
+/// The title and derivation id of a saved service are kept, see `rename_service()`.
 /// ### Params
 /// - `conn`: Rusqlite connection to database
-/// - `service_title`: string representation of the service title
-/// - `pass_num`: password number to update to
+/// - `username`: the user the service belongs to
+/// - `record`: the service to write
+/// ### Returns
+/// `Error::Database` if the database refused the record
 /// ### Side-effect
 /// Writes to database
-pub fn update_db(conn: &Connection, service_title: &str, pass_num: u8) {
-    let query_service = format!("INSERT OR REPLACE INTO services (title, pass_num) VALUES (\"{}\", {})", service_title, pass_num);
-    match conn.execute(query_service.as_str(), ()) {
-        Ok(_) => (),
-        Err(_) => ()
+pub fn update_db(conn: &Connection, username: &str, record: &ServiceRecord) -> crate::error::Result<()> {
+    // the usage of the service is kept, so the row is updated rather than replaced
+    let updates: Vec<String> = RECORD_COLUMNS.split(", ")
+        .filter(|column| !FIXED_COLUMNS.contains(column))
+        .map(|column| format!("{column} = excluded.{column}"))
+        .collect();
+    let query_service = format!("INSERT INTO services (username, {}) VALUES (?17, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+        ON CONFLICT(username, title) DO UPDATE SET {}", RECORD_COLUMNS, updates.join(", "));
+    let spec = &record.spec;
+    let (mode, passphrase) = match spec.mode {
+        Mode::Password => ("password", PassphraseSpec::default()),
+        Mode::Passphrase(passphrase) => ("passphrase", passphrase),
+        Mode::Pin => ("pin", PassphraseSpec::default())
     };
+    let separator = passphrase.separator.map(String::from).unwrap_or_default();
+    conn.execute(query_service.as_str(), params![
+        record.title, record.pass_num, spec.algorithm.number(), spec.length, spec.key.to_classes(),
+        mode, separator, passphrase.capitalize, passphrase.digit,
+        spec.charset.symbols(), spec.charset.excluded(), spec.charset.no_lookalikes, spec.rules.to_string(),
+        record.derivation_id, record.notes, record.tags.join(", "), username
+    ])?;
+    Ok(())
+}
+
+/// Reads the record of a previously used service, following aliases
+/// ### Params
+/// - `conn`: Rusqlite connection
+/// - `username`: the user the service belongs to
+/// - `service_title`: title or alias of the service to look up
+/// ### Returns
+/// The record, titled with the title the alias belongs to, or None if the user has not used the service before
+/// ### Side-effect
+/// Reads from database
+pub fn read_service(conn: &Connection, username: &str, service_title: &str) -> Option<ServiceRecord> {
+    let query = format!("SELECT {} FROM services WHERE username = ?1 AND title = COALESCE(
+        (SELECT title FROM aliases WHERE username = ?1 AND alias = ?2), ?2)", RECORD_COLUMNS);
+    conn.query_row(query.as_str(), params![username, service_title], record_from_row).ok()
+}
+
+/// Gets the previously recorded password number from the database for given service.
+/// ### Params
+/// `conn`: Rusqlite Connection
+/// `username`: the user the service belongs to
+/// `service_title`: The record to look up for associated password number value
+/// ### Returns
+/// The password number associated with the service title or 1 (default) if service does not exist
+/// ### Side-effect
+/// Reads from database
+pub fn read_pass_num(conn: &Connection, username: &str, service_title: &str) -> u8 {
+    let query = "SELECT (pass_num) FROM services WHERE username = ?1 AND title = ?2";
+    conn.query_row(query, params![username, service_title], |row| row.get(0)).unwrap_or(1)
+}
+
+/// Reads all service records of a user from table into a vector
+/// ### Params
+/// - `conn`: Rusqlite connection
+/// - `username`: the user the services belong to
+/// ### Returns
+/// `Result<Vec<ServiceRecord>>` ordered by title
+/// ### Side-effect
+/// Reads from database
+pub fn read_all_services(conn: &Connection, username: &str) -> Result<Vec<ServiceRecord>> {
+    let query = format!("SELECT {} FROM services WHERE username = ?1 ORDER BY title", RECORD_COLUMNS);
+    let services: Vec<ServiceRecord> = conn
+        .prepare(query.as_str())?
+        .query_map(params![username], record_from_row)?
+        .collect::<Result<_>>()?;
+    Ok(services)
 }
 
-/// Reads all services from table into a vector
+/// Reads all services of a user from table into a vector
 /// ### Params
 /// - `conn`: Rusqlite connection
+/// - `username`: the user the services belong to
 /// ### Returns
 /// `Result<Vec<String>>` so that the errors are passed onto the function that calls this one
 /// ### Side-effect
 /// Reads from database
-pub fn read_all_rows(conn: &Connection) -> Result<Vec<String>> {
-    // Prepare a query to select all service titles from the services table
-    let query = "SELECT title FROM services";
+pub fn read_all_rows(conn: &Connection, username: &str) -> Result<Vec<String>> {
+    // Prepare a query to select the user's service titles from the services table
+    let query = "SELECT title FROM services WHERE username = ?1";
     // Execute the query and collect the results into a vector of strings
     let titles: Vec<String> = conn
         .prepare(query)?
-        .query_map([], |row| row.get(0))?
+        .query_map(params![username], |row| row.get(0))?
         .collect::<Result<_>>()?;
     // Return the vector of titles
     Ok(titles)
 }
 
-/// Applies a regular expression to the given array slice containing Strings
-/// and returns a vector of length at most three strings from the array slice that
-/// satisfy the regular expression.
+/// Counts the services saved before services belonged to users
+/// ### Side-effect
+/// Reads from database
+pub fn count_unowned(conn: &Connection) -> Result<usize> {
+    conn.query_row("SELECT COUNT(*) FROM services WHERE username IS NULL", [], |row| row.get(0))
+}
+
+/// Checks whether a service of the title was saved before services belonged to users
+/// and is not assigned yet
+/// ### Side-effect
+/// Reads from database
+pub fn is_unowned(conn: &Connection, title: &str) -> Result<bool> {
+    conn.query_row("SELECT COUNT(*) FROM services WHERE username IS NULL AND title = ?1", params![title],
+                   |row| row.get::<_, u32>(0).map(|count| count > 0))
+}
+
+/// Gives the services saved before services belonged to users to a user. Services
+/// the user already has a record of keep that record and stay unowned.
 /// ### Params
-/// - `titles`: the array slice containing the titles of the services
-/// - `pattern`: the regular expression pattern to look for in the given array slice
+/// - `conn`: Rusqlite connection
+/// - `username`: the user the services are given to
 /// ### Returns
-/// `Result<Vec<String>> of at most 3 Strings that satisfy the given regex
-/// ### Side-effect:
+/// The number of services given to the user
+/// ### Side-effect
+/// Writes to database
+pub fn assign_unowned(conn: &Connection, username: &str) -> Result<usize> {
+    conn.execute("UPDATE OR IGNORE services SET username = ?1 WHERE username IS NULL", params![username])
+}
+
+/// Counts a use of the service and remembers when it was, for ranking search results
+/// ### Params
+/// - `conn`: Rusqlite connection
+/// - `username`: the user the service belongs to
+/// - `service_title`: title of the used service
+/// - `now`: the current Unix time in seconds
+/// ### Side-effect
+/// Writes to database
+pub fn record_use(conn: &Connection, username: &str, service_title: &str, now: i64) -> Result<()> {
+    conn.execute("UPDATE services SET use_count = use_count + 1, last_used = ?3 WHERE username = ?1 AND title = ?2",
+                 params![username, service_title, now])?;
+    Ok(())
+}
+
+/// Reads how often and when every service of a user was used
+/// ### Params
+/// - `conn`: Rusqlite connection
+/// - `username`: the user the services belong to
+/// ### Returns
+/// The usage by service title
+/// ### Side-effect
 /// Reads from database
-pub fn apply_regex(titles: &[String], pattern: &str) -> Result<Vec<String>> {
-    // Create a regex object from the pattern
-    let re = Regex::new(pattern).unwrap();
-    // Create an empty vector to store the matching titles
-    let mut matches = Vec::new();
-    // Iterate over the titles and check if they match the pattern
-    for title in titles {
-        if re.is_match(&title) {
-            // If the title matches, push it to the vector of matches
-            matches.push(title.clone());
-            // If the vector of matches has reached 3, break the loop
-            if matches.len() == 3 {
-                break;
-            }
-        }
+pub fn read_usage(conn: &Connection, username: &str) -> Result<HashMap<String, Usage>> {
+    conn.prepare("SELECT title, last_used, use_count FROM services WHERE username = ?1")?
+        .query_map(params![username], |row| Ok((row.get(0)?, Usage { last_used: row.get(1)?, count: row.get(2)? })))?
+        .collect()
+}
+
+/// Another name of a service, sharing its recipe and password
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alias {
+    pub alias: String,
+    /// Title of the service the alias belongs to
+    pub title: String
+}
+
+/// Reads the aliases of the services of a user
+/// ### Returns
+/// The aliases ordered by alias
+/// ### Side-effect
+/// Reads from database
+pub fn read_aliases(conn: &Connection, username: &str) -> Result<Vec<Alias>> {
+    conn.prepare("SELECT alias, title FROM aliases WHERE username = ?1 ORDER BY alias")?
+        .query_map(params![username], |row| Ok(Alias { alias: row.get(0)?, title: row.get(1)? }))?
+        .collect()
+}
+
+/// Checks that a user has a service of the title, not counting aliases
+/// ### Returns
+/// The title in the case it is saved in
+fn require_service(conn: &Connection, username: &str, title: &str) -> crate::error::Result<String> {
+    let query = "SELECT title FROM services WHERE username = ?1 AND title = ?2";
+    let stored: Vec<String> = conn.prepare(query)?
+        .query_map(params![username, title], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+    stored.into_iter().next().ok_or_else(|| Error::Service(format!("there is no service '{}'", title)))
+}
+
+/// Checks that a name is neither the title nor the alias of a service of the user
+fn require_unused(conn: &Connection, username: &str, name: &str) -> crate::error::Result<()> {
+    let query = "SELECT (SELECT COUNT(*) FROM services WHERE username = ?1 AND title = ?2)
+                      + (SELECT COUNT(*) FROM aliases WHERE username = ?1 AND alias = ?2)";
+    let count: u32 = conn.query_row(query, params![username, name], |row| row.get(0))?;
+    if name.is_empty() {
+        return Err(Error::Service("the name cannot be empty".to_string()));
+    }
+    match count {
+        0 => Ok(()),
+        _ => Err(Error::Service(format!("'{}' is already the name of a service", name)))
+    }
+}
+
+/// Renames a service, also just to change the case of its title. Its password is
+/// unchanged since it keeps its derivation id.
+/// ### Params
+/// - `conn`: Rusqlite connection
+/// - `username`: the user the service belongs to
+/// - `title`: the current title
+/// - `new_title`: the title to show from now on, not used by another service or alias
+/// ### Returns
+/// `Error::Service` if the service does not exist or the new title is taken
+/// ### Side-effect
+/// Writes to database, moves the aliases of the service to the new title
+pub fn rename_service(conn: &Connection, username: &str, title: &str, new_title: &str) -> crate::error::Result<()> {
+    require_service(conn, username, title)?;
+    if !new_title.eq_ignore_ascii_case(title) {
+        require_unused(conn, username, new_title)?;
+    }
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("UPDATE services SET title = ?3 WHERE username = ?1 AND title = ?2", params![username, title, new_title])?;
+    tx.execute("UPDATE aliases SET title = ?3 WHERE username = ?1 AND title = ?2", params![username, title, new_title])?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Changes the notes and tags of a saved service without touching its recipe
+/// ### Returns
+/// `Error::Service` if the service does not exist
+/// ### Side-effect
+/// Writes to database
+pub fn update_details(conn: &Connection, username: &str, title: &str, notes: &str, tags: &[String]) -> crate::error::Result<()> {
+    require_service(conn, username, title)?;
+    conn.execute("UPDATE services SET notes = ?3, tags = ?4 WHERE username = ?1 AND title = ?2",
+                 params![username, title, notes, tags.join(", ")])?;
+    Ok(())
+}
+
+/// Forgets a service and its aliases, e.g. a misspelled title
+/// ### Returns
+/// `Error::Service` if the service does not exist
+/// ### Side-effect
+/// Writes to database
+pub fn delete_service(conn: &Connection, username: &str, title: &str) -> crate::error::Result<()> {
+    require_service(conn, username, title)?;
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM services WHERE username = ?1 AND title = ?2", params![username, title])?;
+    tx.execute("DELETE FROM aliases WHERE username = ?1 AND title = ?2", params![username, title])?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Gives a service another name, e.g. `google` for `gmail`. Both share the recipe and the password.
+/// ### Params
+/// - `alias`: the other name, not used by another service or alias
+/// - `title`: title or alias of the service
+/// ### Returns
+/// `Error::Service` if the service does not exist or the alias is taken
+/// ### Side-effect
+/// Writes to database
+pub fn add_alias(conn: &Connection, username: &str, alias: &str, title: &str) -> crate::error::Result<()> {
+    let title = read_service(conn, username, title)
+        .ok_or_else(|| Error::Service(format!("there is no service '{}'", title)))?
+        .title;
+    require_unused(conn, username, alias)?;
+    conn.execute("INSERT INTO aliases (username, alias, title) VALUES (?1, ?2, ?3)", params![username, alias, title])?;
+    Ok(())
+}
+
+/// Forgets another name of a service, the service itself is kept
+/// ### Returns
+/// `Error::Service` if there is no such alias
+/// ### Side-effect
+/// Writes to database
+pub fn remove_alias(conn: &Connection, username: &str, alias: &str) -> crate::error::Result<()> {
+    match conn.execute("DELETE FROM aliases WHERE username = ?1 AND alias = ?2", params![username, alias])? {
+        0 => Err(Error::Service(format!("there is no alias '{}'", alias))),
+        _ => Ok(())
+    }
+}
+
+/// Merges a duplicate into a service: the duplicate becomes an alias of the service,
+/// so both give the password of the service, and their usage is added up.
+/// The password of the duplicate is no longer generated.
+/// ### Params
+/// - `duplicate`: title of the service to merge away
+/// - `title`: title or alias of the service to keep
+/// ### Returns
+/// `Error::Service` if either service does not exist or they are the same
+/// ### Side-effect
+/// Writes to database, moves the aliases of the duplicate to the service
+pub fn merge_services(conn: &Connection, username: &str, duplicate: &str, title: &str) -> crate::error::Result<()> {
+    let duplicate = require_service(conn, username, duplicate)?;
+    let title = read_service(conn, username, title)
+        .ok_or_else(|| Error::Service(format!("there is no service '{}'", title)))?
+        .title;
+    // titles are compared ignoring case like the database does
+    if title.eq_ignore_ascii_case(&duplicate) {
+        return Err(Error::Service(format!("cannot merge '{}' into itself", title)));
     }
-    // Return the vector of matches
-    Ok(matches)
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("UPDATE services SET
+            use_count = use_count + (SELECT use_count FROM services WHERE username = ?1 AND title = ?2),
+            last_used = (SELECT MAX(last_used) FROM services WHERE username = ?1 AND title IN (?2, ?3))
+        WHERE username = ?1 AND title = ?3", params![username, duplicate, title])?;
+    tx.execute("DELETE FROM services WHERE username = ?1 AND title = ?2", params![username, duplicate])?;
+    tx.execute("UPDATE aliases SET title = ?3 WHERE username = ?1 AND title = ?2", params![username, duplicate, title])?;
+    tx.execute("INSERT INTO aliases (username, alias, title) VALUES (?1, ?2, ?3)", params![username, duplicate, title])?;
+    tx.commit()?;
+    Ok(())
 }
@@ -0,0 +1,89 @@
+//! Session mode of the interactive password manager. The master key is kept
+//! while the user is active so passwords of `Algorithm::V3` are generated
+//! without asking for the master password, and wiped after being idle.
+
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use project_stella::MasterKey;
+
+/// Idle time of a locked session, replaced by the one chosen when it is unlocked
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// How often the idle timer is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+struct State {
+    key: Option<MasterKey>,
+    last_used: Instant,
+    timeout: Duration
+}
+
+/// Holds the master key of an unlocked session. A background thread wipes
+/// the key once the session has been idle for longer than its timeout.
+pub struct Session {
+    state: Arc<Mutex<State>>
+}
+
+impl Session {
+    /// Makes a locked session and starts its idle timer
+    /// ### Side-effect
+    /// Spawns a thread that lives as long as the session
+    pub fn new() -> Self {
+        let state = Arc::new(Mutex::new(State {
+            key: None,
+            last_used: Instant::now(),
+            timeout: DEFAULT_IDLE_TIMEOUT
+        }));
+        let weak = Arc::downgrade(&state);
+        std::thread::spawn(move || idle_timer(weak));
+        Session { state }
+    }
+
+    /// Keeps the master key until the session is locked or idle for `timeout`
+    pub fn unlock(&self, key: MasterKey, timeout: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.key = Some(key);
+        state.timeout = timeout;
+        state.last_used = Instant::now();
+    }
+
+    /// Wipes the master key
+    /// ### Returns
+    /// true if the session was unlocked
+    pub fn lock(&self) -> bool {
+        self.state.lock().unwrap().key.take().is_some()
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.state.lock().unwrap().key.is_some()
+    }
+
+    /// Restarts the idle timer
+    pub fn touch(&self) {
+        self.state.lock().unwrap().last_used = Instant::now();
+    }
+
+    /// Runs `f` with the master key and restarts the idle timer
+    /// ### Returns
+    /// What `f` returns or None if the session is locked
+    pub fn with_key<R>(&self, f: impl FnOnce(&MasterKey) -> R) -> Option<R> {
+        let mut state = self.state.lock().unwrap();
+        state.last_used = Instant::now();
+        state.key.as_ref().map(f)
+    }
+}
+
+/// Wipes the key of the session once it has been idle for too long. Stops when the session is dropped.
+fn idle_timer(state: Weak<Mutex<State>>) {
+    while let Some(state) = state.upgrade() {
+        {
+            let mut state = state.lock().unwrap();
+            if state.key.is_some() && state.last_used.elapsed() >= state.timeout {
+                state.key = None;
+            }
+        }
+        drop(state);
+        std::thread::sleep(CHECK_INTERVAL);
+    }
+}
@@ -0,0 +1,101 @@
+//! Deterministic stream of bytes expanded from an Argon2 seed with keyed
+//! BLAKE2b in counter mode. Lets the generator draw as many random bytes as
+//! it needs without running Argon2 again.
+
+use blake2b_simd::Params;
+use zeroize::Zeroize;
+
+/// Bytes produced per BLAKE2b call (and the maximum key length)
+const BLOCK_LEN: usize = 64;
+
+/// Separates this use of BLAKE2b from any other use of the same seed
+const PERSONAL: &[u8; 16] = b"stellar-stream-1";
+
+/// An endless, reproducible stream of bytes. The seed and the buffered
+/// bytes are zeroized when the stream is dropped.
+pub struct KeyStream {
+    seed: [u8; BLOCK_LEN],
+    seed_len: usize,
+    context: Vec<u8>,
+    block: [u8; BLOCK_LEN],
+    pos: usize,
+    counter: u64
+}
+
+impl KeyStream {
+    /// Makes a stream from a seed
+    /// ### Params
+    /// - `seed`   : secret seed of at most 64 bytes, usually a raw Argon2 hash
+    /// - `context`: what the bytes are used for. Different contexts give unrelated
+    ///   streams from the same seed.
+    pub fn new(seed: &[u8], context: &str) -> Self {
+        assert!(seed.len() <= BLOCK_LEN, "seed is longer than {} bytes", BLOCK_LEN);
+        let mut stream = KeyStream {
+            seed: [0; BLOCK_LEN],
+            seed_len: seed.len(),
+            context: context.as_bytes().to_vec(),
+            block: [0; BLOCK_LEN],
+            pos: BLOCK_LEN,
+            counter: 0
+        };
+        stream.seed[..seed.len()].copy_from_slice(seed);
+        stream
+    }
+
+    /// Computes the next block of the stream
+    fn refill(&mut self) {
+        let hash = Params::new()
+            .hash_length(BLOCK_LEN)
+            .key(&self.seed[..self.seed_len])
+            .personal(PERSONAL)
+            .to_state()
+            .update(&self.context)
+            .update(&self.counter.to_le_bytes())
+            .finalize();
+        self.block.copy_from_slice(hash.as_bytes());
+        self.counter += 1;
+        self.pos = 0;
+    }
+
+    /// Takes the next byte off the stream
+    pub fn next_byte(&mut self) -> u8 {
+        if self.pos == BLOCK_LEN {
+            self.refill();
+        }
+        let byte = self.block[self.pos];
+        self.pos += 1;
+        byte
+    }
+
+    /// Takes the next 4 bytes off the stream as a big-endian integer
+    pub fn next_u32(&mut self) -> u32 {
+        u32::from_be_bytes([self.next_byte(), self.next_byte(), self.next_byte(), self.next_byte()])
+    }
+
+    /// Draws a uniformly distributed index below `bound` by rejection sampling:
+    /// integers from the incomplete last multiple of `bound` are thrown away
+    /// instead of being folded onto the lower indices with a modulo.
+    /// ### Params
+    /// - `bound`: number of possible indices, must not be 0
+    /// ### Returns
+    /// An index in `0..bound`
+    pub fn next_index(&mut self, bound: usize) -> usize {
+        assert!(bound > 0 && bound as u64 <= u32::MAX as u64, "index bound out of range");
+        let bound = bound as u64;
+        let range = u32::MAX as u64 + 1;
+        let zone = range - range % bound;
+        loop {
+            let value = self.next_u32() as u64;
+            if value < zone {
+                return (value % bound) as usize;
+            }
+        }
+    }
+}
+
+impl Drop for KeyStream {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+        self.block.zeroize();
+    }
+}
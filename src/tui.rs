@@ -0,0 +1,1125 @@
+//! The full-screen n_curses interface of the interactive password manager. The
+//! services of the user are listed on the left, the recipe of the selected service
+//! is shown on the right and a status bar with the shortcuts is at the bottom.
+//! Changes to a recipe are remembered once a password is generated with it.
+
+extern crate ncurses;
+
+use std::time::Duration;
+
+use ncurses::*;
+use rusqlite::Connection;
+use zeroize::Zeroize;
+
+use project_stella::{auth, display, password_change, Algorithm, Key, Mode, PassphraseSpec, Rules};
+use project_stella::presets::Presets;
+use project_stella::search::{self, Usage};
+use project_stella::service_db_actions::{self, Alias, ServiceRecord};
+
+use crate::clipboard::Backend;
+use crate::clipboard_timer::ClipboardTimer;
+use crate::session::Session;
+use crate::{reveal, user_inputs};
+
+/// Smallest terminal the interface is drawn in
+const MIN_WIDTH: i32 = 60;
+const MIN_HEIGHT: i32 = 12;
+
+/// Milliseconds to wait for a key before the countdowns are redrawn
+const TICK_MS: i32 = 1000;
+
+const ENTER: u32 = 10;
+const RETURN: u32 = 13;
+const ESCAPE: u32 = 27;
+const BACKSPACE: u32 = 127;
+const CTRL_H: u32 = 8;
+
+/// Shortcuts shown in the status bar
+const SHORTCUTS: &str = "g copy  v show  r QR  +/- number  e edit  / search  ? help  q quit  \
+                         n rename  d delete  a aliases  m merge  s session  l lock  p passwd  c config";
+
+/// Where a generated password goes
+enum Output {
+    /// Copied to the clipboard
+    Clipboard,
+    /// Shown on screen, also in groups for reading it aloud
+    Reveal,
+    /// Shown on screen as a QR code for a phone
+    Qr
+}
+
+/// The pane the arrow keys and typed characters go to
+#[derive(Clone, Copy, PartialEq)]
+enum Focus {
+    Services,
+    Search,
+    Recipe
+}
+
+/// A line of the recipe pane that can be edited
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Number,
+    Mode,
+    Length,
+    Classes,
+    Symbols,
+    Excluded,
+    Lookalikes,
+    Rules,
+    Separator,
+    Capitalize,
+    Digit,
+    Algorithm,
+    Notes,
+    Tags
+}
+
+/// A service in the list
+struct Entry {
+    record: ServiceRecord,
+    /// false for new services and changed recipes until a password is generated with them
+    saved: bool,
+    usage: Usage
+}
+
+/// Everything the interface shows
+struct App {
+    username: String,
+    auth_conn: Connection,
+    services_conn: Connection,
+    /// Services of the user ordered by title
+    entries: Vec<Entry>,
+    /// Other names of the saved services
+    aliases: Vec<Alias>,
+    /// Text typed into the search line
+    search: String,
+    /// Indices of the entries matching the search, best match first
+    visible: Vec<usize>,
+    /// Selected line of `visible`, one past its end for adding the searched title as a new service
+    selected: usize,
+    /// First line of `visible` in the list
+    scroll: usize,
+    focus: Focus,
+    /// Selected line of the recipe pane
+    field: usize,
+    status: String,
+    session: Session,
+    clipboard: ClipboardTimer,
+    /// Session and clipboard state at the last tick, to tell when their timers ran out
+    was_unlocked: bool,
+    was_copied: bool
+}
+
+/// Starts the interface and runs it until the user quits
+/// ### Params
+/// - `username` : name of the logged in user
+/// - `clipboard`: where the generated passwords will be delivered
+/// ### Side-effect
+/// Takes over the terminal, reads from and writes to the databases
+pub fn run(username: &str, clipboard: Box<dyn Backend>) {
+    user_inputs::assign_unowned_services(username);
+
+    let mut app = App {
+        username: username.to_string(),
+        auth_conn: user_inputs::auth_connection(),
+        services_conn: user_inputs::services_connection(),
+        entries: Vec::new(),
+        aliases: Vec::new(),
+        search: String::new(),
+        visible: Vec::new(),
+        selected: 0,
+        scroll: 0,
+        focus: Focus::Services,
+        field: 0,
+        status: "Type / to search for a service or add a new one, ? for help.".to_string(),
+        session: Session::new(),
+        clipboard: ClipboardTimer::new(clipboard),
+        was_unlocked: false,
+        was_copied: false
+    };
+    app.load_services();
+    if password_change::is_pending(&app.auth_conn, username) {
+        app.status = "Some services still use your old password. Press p to continue changing them.".to_string();
+    }
+
+    initscr();
+    noecho();
+    cbreak();
+    keypad(stdscr(), true);
+    set_escdelay(25);
+    curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+
+    app.main_loop();
+
+    app.clipboard.clear_now();
+    erase();
+    refresh();
+    curs_set(CURSOR_VISIBILITY::CURSOR_VISIBLE);
+    endwin();
+}
+
+impl App {
+    /// Reads the services of the user, keeping the ones not saved yet
+    fn load_services(&mut self) {
+        let unsaved: Vec<Entry> = self.entries.drain(..).filter(|entry| !entry.saved).collect();
+        let saved = service_db_actions::read_all_services(&self.services_conn, &self.username).unwrap_or_else(|err| {
+            self.status = format!("Failed to read the services: {}", err);
+            Vec::new()
+        });
+        let usage = service_db_actions::read_usage(&self.services_conn, &self.username).unwrap_or_default();
+        let saved: Vec<Entry> = saved.into_iter()
+            .filter(|record| !unsaved.iter().any(|entry| entry.record.title.eq_ignore_ascii_case(&record.title)))
+            .map(|record| {
+                let usage = usage.get(&record.title).copied().unwrap_or_default();
+                Entry { record, saved: true, usage }
+            })
+            .collect();
+        self.entries = saved.into_iter().chain(unsaved).collect();
+        self.entries.sort_by(|a, b| a.record.title.cmp(&b.record.title));
+        self.aliases = service_db_actions::read_aliases(&self.services_conn, &self.username).unwrap_or_default();
+        self.filter();
+    }
+
+    /// Lists the services matching the search, best match first, and selects the first one.
+    /// Services are also found by their aliases and tags. Without a search every service is listed by title.
+    fn filter(&mut self) {
+        let search = self.search.trim();
+        self.visible = if search.is_empty() {
+            (0..self.entries.len()).collect()
+        } else {
+            let titles = self.entries.iter().enumerate().map(|(index, entry)| (index, entry.record.title.as_str(), entry.usage));
+            let aliases = self.aliases.iter().filter_map(|alias| {
+                let index = self.entries.iter().position(|entry| entry.record.title.eq_ignore_ascii_case(&alias.title))?;
+                Some((index, alias.alias.as_str(), self.entries[index].usage))
+            });
+            let tags = self.entries.iter().enumerate()
+                .flat_map(|(index, entry)| entry.record.tags.iter().map(move |tag| (index, tag.as_str(), entry.usage)));
+            let mut ranked = search::rank(search, titles.chain(aliases).chain(tags), search::now());
+            // a service found by its title, an alias or a tag is listed where it ranks best
+            let mut seen = vec![false; self.entries.len()];
+            ranked.retain(|index| !std::mem::replace(&mut seen[*index], true));
+            ranked
+        };
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    /// The searched title if no service has it as its title or alias yet, so it can be added
+    fn new_title(&self) -> Option<String> {
+        let title = self.search.trim();
+        (!title.is_empty() && !self.name_taken(title)).then(|| title.to_string())
+    }
+
+    /// Whether a service has the name as its title or alias, ignoring case
+    fn name_taken(&self, name: &str) -> bool {
+        self.entries.iter().any(|entry| entry.record.title.eq_ignore_ascii_case(name))
+            || self.aliases.iter().any(|alias| alias.alias.eq_ignore_ascii_case(name))
+    }
+
+    /// Lines of the service list: the matching services and the line for adding the searched title
+    fn rows(&self) -> usize {
+        self.visible.len() + usize::from(self.new_title().is_some())
+    }
+
+    /// Selects the service with the title, clearing the search if it hides it
+    fn select_title(&mut self, title: &str) {
+        if !self.visible.iter().any(|index| self.entries[*index].record.title.eq_ignore_ascii_case(title)) {
+            self.search.clear();
+            self.filter();
+        }
+        if let Some(position) = self.visible.iter().position(|index| self.entries[*index].record.title.eq_ignore_ascii_case(title)) {
+            self.selected = position;
+        }
+    }
+
+    /// Index into `entries` of the selected service
+    fn current(&self) -> Option<usize> {
+        self.visible.get(self.selected).copied()
+    }
+
+    /// Handles keys until the user quits
+    fn main_loop(&mut self) {
+        loop {
+            self.tick();
+            self.draw();
+            timeout(TICK_MS);
+            let Some(key) = get_wch() else {
+                continue;
+            };
+            // resizing is not using the password manager, it only needs a redraw
+            if !matches!(key, WchResult::KeyCode(KEY_RESIZE)) {
+                self.session.touch();
+            }
+            let quit = match self.focus {
+                Focus::Search => {
+                    self.search_key(key);
+                    false
+                },
+                Focus::Services | Focus::Recipe => self.command_key(key)
+            };
+            if quit {
+                return;
+            }
+        }
+    }
+
+    /// Tells the user when the session locked or the clipboard was cleared on their own
+    fn tick(&mut self) {
+        let unlocked = self.session.is_unlocked();
+        if self.was_unlocked && !unlocked {
+            self.status = "Session locked after being idle. Press s to unlock it again.".to_string();
+        }
+        self.was_unlocked = unlocked;
+
+        let copied = self.clipboard.seconds_left().is_some();
+        if self.was_copied && !copied {
+            self.status = "Clipboard timeout passed, the password was cleared unless something else was copied.".to_string();
+        }
+        self.was_copied = copied;
+    }
+
+    /// Handles a key typed into the search line
+    fn search_key(&mut self, key: WchResult) {
+        match key {
+            WchResult::Char(ENTER) | WchResult::Char(RETURN) | WchResult::KeyCode(KEY_ENTER) => {
+                self.focus = Focus::Services;
+                if let (None, Some(title)) = (self.current(), self.new_title()) {
+                    self.add_service(&title);
+                }
+            },
+            WchResult::Char(ESCAPE) => {
+                self.search.clear();
+                self.filter();
+                self.focus = Focus::Services;
+            },
+            WchResult::Char(BACKSPACE) | WchResult::Char(CTRL_H) | WchResult::KeyCode(KEY_BACKSPACE) => {
+                self.search.pop();
+                self.filter();
+            },
+            // the next loop redraws for the new size
+            WchResult::KeyCode(KEY_RESIZE) => (),
+            WchResult::KeyCode(code) => self.move_selection(code),
+            WchResult::Char(c) => {
+                if let Some(c) = char::from_u32(c).filter(|c| !c.is_control()) {
+                    self.search.push(c);
+                    self.filter();
+                }
+            }
+        }
+    }
+
+    /// Handles a shortcut
+    /// ### Returns
+    /// true if the user quits
+    fn command_key(&mut self, key: WchResult) -> bool {
+        let c = match key {
+            WchResult::KeyCode(KEY_UP) | WchResult::KeyCode(KEY_DOWN) if self.focus == Focus::Recipe => {
+                let fields = self.fields().len();
+                self.field = match key {
+                    WchResult::KeyCode(KEY_UP) => (self.field + fields - 1) % fields,
+                    _ => (self.field + 1) % fields
+                };
+                return false;
+            },
+            WchResult::KeyCode(KEY_ENTER) => '\n',
+            // the next loop redraws for the new size
+            WchResult::KeyCode(KEY_RESIZE) => return false,
+            WchResult::KeyCode(code) => {
+                self.move_selection(code);
+                return false;
+            },
+            WchResult::Char(c) => char::from_u32(c).unwrap_or_default()
+        };
+
+        match c {
+            '\n' | '\r' if self.focus == Focus::Recipe => self.edit_field(),
+            '\n' | '\r' if self.current().is_none() && self.new_title().is_some() => {
+                let title = self.new_title().unwrap_or_default();
+                self.add_service(&title);
+            },
+            '\n' | '\r' | 'g' => self.generate(Output::Clipboard),
+            'v' => self.generate(Output::Reveal),
+            'r' => self.generate(Output::Qr),
+            '+' | '=' => self.change_number(1),
+            '-' => self.change_number(-1),
+            'e' | '\t' => self.toggle_recipe_focus(),
+            '\u{1b}' => self.focus = Focus::Services,
+            '/' => {
+                self.focus = Focus::Search;
+                self.status = "Type a part of the title, Enter selects the best match or adds the title as a new service, Esc clears the search.".to_string();
+            },
+            's' => self.unlock_session(),
+            'l' => {
+                self.status = if self.session.lock() { "Session locked." } else { "Session is not unlocked." }.to_string();
+                self.was_unlocked = false;
+            },
+            'n' => self.rename_service(),
+            'd' => self.delete_service(),
+            'a' => self.edit_aliases(),
+            'm' => self.merge_service(),
+            'p' => self.suspend(user_inputs::change_password),
+            'c' => reveal::show("Settings in effect", crate::commands::config_lines()),
+            '?' => reveal::show("Shortcuts", help_lines()),
+            'q' => return true,
+            _ => ()
+        }
+        false
+    }
+
+    /// Moves the selection in the service list
+    fn move_selection(&mut self, code: i32) {
+        let last = self.rows().saturating_sub(1);
+        let page = (LINES() - 6).max(1) as usize;
+        self.selected = match code {
+            KEY_UP => self.selected.saturating_sub(1),
+            KEY_DOWN => (self.selected + 1).min(last),
+            KEY_PPAGE => self.selected.saturating_sub(page),
+            KEY_NPAGE => (self.selected + page).min(last),
+            KEY_HOME => 0,
+            KEY_END => last,
+            _ => self.selected
+        };
+        self.field = 0;
+    }
+
+    fn toggle_recipe_focus(&mut self) {
+        if self.current().is_none() {
+            self.status = "No service selected. Press / to search for one or add it.".to_string();
+            return;
+        }
+        self.focus = match self.focus {
+            Focus::Recipe => Focus::Services,
+            _ => {
+                self.status = "Up and down choose a setting, Enter changes it, e or Esc goes back to the services.".to_string();
+                Focus::Recipe
+            }
+        };
+    }
+
+    /// Adds a service that has not been used before, filled in with its site preset
+    fn add_service(&mut self, title: &str) {
+        let mut record = ServiceRecord::new(title, 1);
+        // another service may still derive its password from the title, e.g. after a rename
+        match service_db_actions::unused_derivation_id(&self.services_conn, &self.username, title) {
+            Ok(id) => record.derivation_id = id,
+            Err(err) => {
+                self.status = format!("Cannot add '{}', {}.", title, err);
+                return;
+            }
+        }
+        record.spec = crate::config().spec();
+        let presets = Presets::load(crate::paths().presets()).unwrap_or_else(|err| {
+            self.status = format!("Ignoring user presets, {}", err);
+            Presets::bundled()
+        });
+        self.status = format!("Added '{}', generate a password to remember it.", title);
+        if let Some(preset) = presets.find(title) {
+            let mut spec = record.spec;
+            match preset.apply(&mut spec) {
+                Ok(()) => {
+                    record.spec = spec;
+                    self.status = format!("Added '{}' with its site preset, generate a password to remember it.", title);
+                },
+                Err(err) => self.status = format!("Ignoring the site preset for '{}', {}", title, err)
+            }
+        }
+        self.entries.push(Entry { record, saved: false, usage: Usage::default() });
+        self.entries.sort_by(|a, b| a.record.title.cmp(&b.record.title));
+        self.search.clear();
+        self.filter();
+        self.select_title(title);
+    }
+
+    /// Index of the selected service and whether it is saved in the database
+    fn stored(&self) -> Option<(usize, bool)> {
+        let index = self.current()?;
+        let title = &self.entries[index].record.title;
+        Some((index, service_db_actions::read_service(&self.services_conn, &self.username, title).is_some()))
+    }
+
+    /// Renames the selected service, e.g. to fix a typo. Its password stays the same.
+    fn rename_service(&mut self) {
+        let Some((index, stored)) = self.stored() else {
+            self.status = "No service selected.".to_string();
+            return;
+        };
+        let title = self.entries[index].record.title.clone();
+        let Some(new_title) = self.prompt(&format!("Rename '{}' to: ", title), &title, false) else {
+            return;
+        };
+        let new_title = new_title.trim().to_string();
+        if new_title == title {
+            return;
+        }
+        let result = if stored {
+            service_db_actions::rename_service(&self.services_conn, &self.username, &title, &new_title)
+                .map_err(|err| err.to_string())
+                .and_then(|()| password_change::rename_service(&self.auth_conn, &self.username, &title, &new_title).map_err(|err| err.to_string()))
+        } else if self.name_taken(&new_title) && !new_title.eq_ignore_ascii_case(&title) {
+            Err(format!("'{}' is already the name of a service", new_title))
+        } else if new_title.is_empty() {
+            Err("the name cannot be empty".to_string())
+        } else {
+            // no password was generated with the old title yet, so the new one is derived from
+            service_db_actions::unused_derivation_id(&self.services_conn, &self.username, &new_title)
+                .map(|id| self.entries[index].record.derivation_id = id)
+                .map_err(|err| err.to_string())
+        };
+        self.status = match result {
+            Ok(()) => {
+                self.entries[index].record.title = new_title.clone();
+                self.load_services();
+                self.select_title(&new_title);
+                format!("Renamed '{}' to '{}', its password is unchanged.", title, new_title)
+            },
+            Err(err) => format!("Kept the name, {}.", err)
+        };
+    }
+
+    /// Forgets the selected service and its aliases, e.g. a misspelled title
+    fn delete_service(&mut self) {
+        let Some((index, stored)) = self.stored() else {
+            self.status = "No service selected.".to_string();
+            return;
+        };
+        let title = self.entries[index].record.title.clone();
+        let answer = self.prompt(&format!("Delete '{}' and its aliases? Adding it again gives the same password. [y/N] ", title), "", false);
+        if !matches!(answer.as_deref().map(str::trim), Some("y" | "yes")) {
+            self.status = "Cancelled.".to_string();
+            return;
+        }
+        if stored {
+            if let Err(err) = service_db_actions::delete_service(&self.services_conn, &self.username, &title) {
+                self.status = format!("Failed to delete '{}', {}.", title, err);
+                return;
+            }
+        }
+        self.entries.remove(index);
+        self.load_services();
+        self.status = format!("Deleted '{}'.", title);
+    }
+
+    /// Edits the other names of the selected service, e.g. google for gmail. They share its recipe and password.
+    fn edit_aliases(&mut self) {
+        let Some((index, stored)) = self.stored() else {
+            self.status = "No service selected.".to_string();
+            return;
+        };
+        let title = self.entries[index].record.title.clone();
+        if !stored {
+            self.status = format!("Generate a password for '{}' before giving it other names.", title);
+            return;
+        }
+        let current = self.aliases_of(&title);
+        let Some(input) = self.prompt(&format!("Other names of '{}', separated by commas: ", title), &current.join(", "), false) else {
+            return;
+        };
+        let wanted: Vec<String> = input.split(',')
+            .map(|alias| alias.trim().to_string())
+            .filter(|alias| !alias.is_empty())
+            .collect();
+
+        let mut errors = Vec::new();
+        for alias in current.iter().filter(|alias| !wanted.contains(alias)) {
+            errors.extend(service_db_actions::remove_alias(&self.services_conn, &self.username, alias).err());
+        }
+        for alias in wanted.iter().filter(|alias| !current.contains(alias)) {
+            errors.extend(service_db_actions::add_alias(&self.services_conn, &self.username, alias, &title).err());
+        }
+        self.load_services();
+        self.select_title(&title);
+        self.status = match errors.first() {
+            None => format!("'{}' is also called {}.", title, none_if_empty(self.aliases_of(&title).join(", "))),
+            Some(err) => format!("Not every name was changed, {}.", err)
+        };
+    }
+
+    /// Merges the selected service into another one: it becomes an alias of the other
+    /// service and gives its password, its own password is no longer generated
+    fn merge_service(&mut self) {
+        let Some((index, stored)) = self.stored() else {
+            self.status = "No service selected.".to_string();
+            return;
+        };
+        let title = self.entries[index].record.title.clone();
+        if !stored {
+            self.status = format!("'{}' is not saved yet, delete it with d instead.", title);
+            return;
+        }
+        let Some(into) = self.prompt(&format!("Merge '{}' into (its own password is no longer generated): ", title), "", false) else {
+            return;
+        };
+        // merging into an alias merges into its service
+        let into = into.trim().to_string();
+        let into = service_db_actions::read_service(&self.services_conn, &self.username, &into).map_or(into, |record| record.title);
+        let result = service_db_actions::merge_services(&self.services_conn, &self.username, &title, &into)
+            .map_err(|err| err.to_string())
+            .and_then(|()| password_change::rename_service(&self.auth_conn, &self.username, &title, &into).map_err(|err| err.to_string()));
+        self.status = match result {
+            Ok(()) => {
+                self.entries.remove(index);
+                self.load_services();
+                self.select_title(&into);
+                format!("Merged '{}' into '{}', both give the password of '{}' now.", title, into, into)
+            },
+            Err(err) => format!("Kept '{}', {}.", title, err)
+        };
+    }
+
+    /// The aliases of a service in order
+    fn aliases_of(&self, title: &str) -> Vec<String> {
+        self.aliases.iter().filter(|alias| alias.title.eq_ignore_ascii_case(title)).map(|alias| alias.alias.clone()).collect()
+    }
+
+    /// Changes the password number of the selected service, e.g. after the password leaked
+    fn change_number(&mut self, by: i16) {
+        let Some(index) = self.current() else {
+            self.status = "No service selected.".to_string();
+            return;
+        };
+        let entry = &mut self.entries[index];
+        let number = (entry.record.pass_num as i16 + by).clamp(1, u8::MAX as i16) as u8;
+        if number != entry.record.pass_num {
+            entry.record.pass_num = number;
+            entry.saved = false;
+        }
+        self.status = format!("Password number {}, generate a password to remember it.", number);
+    }
+
+    /// Generates the password of the selected service and remembers its recipe
+    fn generate(&mut self, output: Output) {
+        let Some(index) = self.current() else {
+            self.status = "No service selected. Press / to search for one or add it.".to_string();
+            return;
+        };
+        let record = &self.entries[index].record;
+        let (title, spec, salt_part) = (record.title.clone(), record.spec, record.salt_part());
+
+        let from_session = match spec.algorithm {
+            Algorithm::V3 => self.session.with_key(|key| key.generate(&[salt_part.as_str()], &spec)),
+            _ => None
+        };
+        let generated = match from_session {
+            Some(generated) => generated,
+            None => {
+                let label = if self.session.is_unlocked() {
+                    format!("Algorithm version {} needs the master password: ", spec.algorithm.number())
+                } else {
+                    "Master password: ".to_string()
+                };
+                let Some(mut password) = self.prompt(&label, "", true) else {
+                    self.status = "Cancelled.".to_string();
+                    return;
+                };
+                if !auth::verify_user(&self.auth_conn, &self.username, &password) {
+                    password.zeroize();
+                    self.status = "Password did not match login password. Try again.".to_string();
+                    return;
+                }
+                let Some(profile) = auth::read_profile(&self.auth_conn, &self.username) else {
+                    password.zeroize();
+                    self.status = "The stored KDF profile is invalid.".to_string();
+                    return;
+                };
+                self.status = "Generating...".to_string();
+                self.draw();
+                profile.generate(&mut password, &[self.username.as_str(), salt_part.as_str()], &spec)
+            }
+        };
+        let mut generated_pass = match generated {
+            Ok(pass) => pass,
+            Err(err) => {
+                self.status = format!("Failed to generate password: {}", err);
+                return;
+            }
+        };
+        // a password whose recipe is not remembered could not be generated again
+        if let Err(err) = service_db_actions::update_db(&self.services_conn, &self.username, &self.entries[index].record) {
+            generated_pass.zeroize();
+            self.status = format!("Did not give the password of '{}', it cannot be remembered: {}.", title, err);
+            return;
+        }
+
+        self.status = match output {
+            // printing would write into the screen and leave the password in the scrollback
+            Output::Clipboard if self.clipboard.prints() => {
+                reveal::show(&title, vec![generated_pass.clone()]);
+                format!("Showed the password of '{}', the clipboard backend is stdout.", title)
+            },
+            Output::Clipboard => {
+                let timeout = crate::config().clipboard_timeout.value;
+                match self.clipboard.copy(&generated_pass, Duration::from_secs(timeout)) {
+                    Ok(()) if self.clipboard.clears() => format!("Copied the password of '{}' to the clipboard.", title),
+                    Ok(()) => format!("Generated the password of '{}'.", title),
+                    Err(reason) => format!("Generated the password of '{}' but {}.", title, reason)
+                }
+            },
+            Output::Reveal => {
+                let mut lines = vec![generated_pass.clone()];
+                if !matches!(spec.mode, Mode::Passphrase(_)) {
+                    lines.push(String::new());
+                    lines.push(display::grouped(&generated_pass, display::GROUP_SIZE));
+                }
+                reveal::show(&title, lines);
+                format!("Showed the password of '{}'.", title)
+            },
+            Output::Qr => match display::qr_code(&generated_pass) {
+                Ok(code) => {
+                    reveal::show(&title, code.lines().map(str::to_string).collect());
+                    format!("Showed the password of '{}' as a QR code.", title)
+                },
+                Err(err) => format!("Generated the password of '{}' but {}", title, err)
+            }
+        };
+        generated_pass.zeroize();
+
+        let entry = &mut self.entries[index];
+        let now = search::now();
+        let _ = service_db_actions::record_use(&self.services_conn, &self.username, &entry.record.title, now);
+        entry.saved = true;
+        entry.usage = Usage { last_used: Some(now), count: entry.usage.count.saturating_add(1) };
+    }
+
+    /// Unlocks session mode: the master password is stretched into the master key once
+    /// and kept until the session is locked or idle for the chosen number of minutes
+    fn unlock_session(&mut self) {
+        let Some(mut password) = self.prompt("Master password: ", "", true) else {
+            self.status = "Cancelled.".to_string();
+            return;
+        };
+        if !auth::verify_user(&self.auth_conn, &self.username, &password) {
+            password.zeroize();
+            self.status = "Password did not match login password. Try again.".to_string();
+            return;
+        }
+        let Some(profile) = auth::read_profile(&self.auth_conn, &self.username) else {
+            password.zeroize();
+            self.status = "The stored KDF profile is invalid.".to_string();
+            return;
+        };
+        let default_minutes = crate::config().session_idle_minutes.value.to_string();
+        let minutes = self.prompt("Lock the session after how many minutes idle? ", &default_minutes, false)
+            .and_then(|minutes| minutes.trim().parse::<u64>().ok())
+            .filter(|minutes| *minutes > 0);
+        let Some(minutes) = minutes else {
+            password.zeroize();
+            self.status = "Not a number of minutes, the session stays locked.".to_string();
+            return;
+        };
+
+        self.status = "Unlocking...".to_string();
+        self.draw();
+        self.status = match profile.master_key(&mut password, &self.username) {
+            Ok(key) => {
+                self.session.unlock(key, Duration::from_secs(minutes * 60));
+                self.was_unlocked = true;
+                "Session unlocked. Services using algorithm version 3 no longer ask for the master password.".to_string()
+            },
+            Err(err) => format!("Failed to unlock the session: {}", err)
+        };
+    }
+
+    /// The lines of the recipe pane of the selected service
+    fn fields(&self) -> Vec<Field> {
+        let mode = self.current().map_or(Mode::Password, |index| self.entries[index].record.spec.mode);
+        let mut fields = vec![Field::Number, Field::Mode, Field::Length];
+        match mode {
+            Mode::Password => fields.extend([Field::Classes, Field::Symbols, Field::Excluded, Field::Lookalikes, Field::Rules]),
+            Mode::Passphrase(_) => fields.extend([Field::Separator, Field::Capitalize, Field::Digit]),
+            Mode::Pin => ()
+        }
+        fields.extend([Field::Algorithm, Field::Notes, Field::Tags]);
+        fields
+    }
+
+    /// Changes the selected line of the recipe, or the notes or tags, of the selected service
+    fn edit_field(&mut self) {
+        let Some(index) = self.current() else {
+            return;
+        };
+        let Some(field) = self.fields().get(self.field).copied() else {
+            return;
+        };
+        let mut record = self.entries[index].record.clone();
+        let (label, value) = match field {
+            Field::Notes => ("Notes", record.notes.clone()),
+            Field::Tags => ("Tags", record.tags.join(", ")),
+            _ => field_text(&record, field)
+        };
+
+        let result = match field {
+            Field::Lookalikes => {
+                record.spec.charset.no_lookalikes = !record.spec.charset.no_lookalikes;
+                Ok(())
+            },
+            Field::Capitalize | Field::Digit => {
+                if let Mode::Passphrase(passphrase) = &mut record.spec.mode {
+                    match field {
+                        Field::Capitalize => passphrase.capitalize = !passphrase.capitalize,
+                        _ => passphrase.digit = !passphrase.digit
+                    }
+                }
+                Ok(())
+            },
+            _ => {
+                let hint = match field {
+                    Field::Mode => " (password, passphrase or pin)",
+                    Field::Classes => " (any of u, l, n and s)",
+                    Field::Symbols => " ('all' for every symbol)",
+                    Field::Excluded | Field::Rules | Field::Separator => " ('none' for none)",
+                    Field::Algorithm => " (1, 2 or 3, changes the password)",
+                    Field::Tags => " (separated by commas)",
+                    _ => ""
+                };
+                let Some(input) = self.prompt(&format!("{}{}: ", label, hint), &value, false) else {
+                    return;
+                };
+                set_field(&mut record, field, input.trim())
+            }
+        };
+
+        if let (Field::Notes | Field::Tags, Ok(())) = (field, &result) {
+            self.save_details(index, record);
+            return;
+        }
+        self.status = match result.and_then(|()| record.spec.validate().map_err(|err| err.to_string())) {
+            Ok(()) => {
+                let entry = &mut self.entries[index];
+                if entry.record.pass_num != record.pass_num || entry.record.spec != record.spec {
+                    entry.record = record;
+                    entry.saved = false;
+                }
+                self.field = self.field.min(self.fields().len() - 1);
+                "Changed the recipe, generate a password to remember it.".to_string()
+            },
+            Err(err) => format!("Kept the recipe, {}.", err)
+        };
+    }
+
+    /// Keeps the changed notes and tags of a service. They are saved right away since
+    /// they do not change the password, unless the service itself is not saved yet.
+    fn save_details(&mut self, index: usize, record: ServiceRecord) {
+        let entry = &mut self.entries[index];
+        let stored = service_db_actions::read_service(&self.services_conn, &self.username, &entry.record.title).is_some();
+        let result = match stored {
+            true => service_db_actions::update_details(&self.services_conn, &self.username, &entry.record.title, &record.notes, &record.tags),
+            false => Ok(())
+        };
+        self.status = match result {
+            Ok(()) => {
+                entry.record.notes = record.notes;
+                entry.record.tags = record.tags;
+                if stored { "Saved the notes and tags." } else { "Changed the notes and tags, generate a password to remember them." }.to_string()
+            },
+            Err(err) => format!("Kept the notes and tags, {}.", err)
+        };
+    }
+
+    /// Leaves the full-screen interface to run a line-based dialog, like changing the master password
+    /// ### Side-effect
+    /// Hands the terminal to `dialog` until it returns and the user presses Enter
+    fn suspend(&mut self, dialog: impl FnOnce(&str, &Session)) {
+        def_prog_mode();
+        endwin();
+        dialog(&self.username, &self.session);
+        user_inputs::get_visible_input_prompt("Press Enter to return.");
+        reset_prog_mode();
+        self.was_unlocked = self.session.is_unlocked();
+        self.status = String::new();
+        self.load_services();
+        clear();
+    }
+
+    /// Asks for a line of input in the status bar
+    /// ### Params
+    /// - `label`: the question
+    /// - `initial`: the answer to start from
+    /// - `hidden`: true for passwords, which are not shown while typed
+    /// ### Returns
+    /// The answer, or None if the user pressed Escape
+    fn prompt(&mut self, label: &str, initial: &str, hidden: bool) -> Option<String> {
+        let mut input = initial.to_string();
+        timeout(-1);
+        curs_set(CURSOR_VISIBILITY::CURSOR_VISIBLE);
+        let answer = loop {
+            self.draw();
+            let row = LINES() - 2;
+            let shown = if hidden { String::new() } else { input.clone() };
+            mvaddstr(row, 0, &fit(&format!("{}{}", label, shown), COLS() as usize));
+            clrtoeol();
+            refresh();
+            match get_wch() {
+                Some(WchResult::Char(ENTER)) | Some(WchResult::Char(RETURN)) | Some(WchResult::KeyCode(KEY_ENTER)) => break Some(input),
+                Some(WchResult::Char(ESCAPE)) => {
+                    input.zeroize();
+                    break None;
+                },
+                Some(WchResult::Char(BACKSPACE)) | Some(WchResult::Char(CTRL_H)) | Some(WchResult::KeyCode(KEY_BACKSPACE)) => {
+                    input.pop();
+                },
+                Some(WchResult::Char(c)) => {
+                    if let Some(c) = char::from_u32(c).filter(|c| !c.is_control()) {
+                        input.push(c);
+                    }
+                },
+                _ => ()
+            }
+        };
+        curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+        answer
+    }
+
+    /// Draws the whole screen for the current size of the terminal
+    fn draw(&mut self) {
+        erase();
+        let (height, width) = (LINES(), COLS());
+        if height < MIN_HEIGHT || width < MIN_WIDTH {
+            mvaddstr(0, 0, &fit(&format!("Make the terminal at least {}x{}, it is {}x{}.", MIN_WIDTH, MIN_HEIGHT, width, height), width as usize));
+            refresh();
+            return;
+        }
+
+        // header with the state of the session and the clipboard
+        let mut state = vec![format!("session {}", if self.session.is_unlocked() { "unlocked" } else { "locked" })];
+        match self.clipboard.seconds_left() {
+            Some(seconds) => state.push(format!("clipboard clears in {}s", seconds)),
+            None => state.push(format!("clipboard: {}", self.clipboard.backend_name()))
+        }
+        let state = state.join(" | ");
+        attron(A_BOLD());
+        mvaddstr(0, 1, &fit(&format!("stellar - {}", self.username), width as usize - 2));
+        attroff(A_BOLD());
+        mvaddstr(0, (width - 1 - state.len() as i32).max(0), &state);
+
+        let pane_height = height - 3;
+        let list_width = (width / 3).max(28);
+        self.draw_services(1, 0, pane_height, list_width);
+        self.draw_recipe(1, list_width, pane_height, width - list_width);
+
+        // status bar and shortcuts
+        attron(A_REVERSE());
+        mvaddstr(height - 2, 0, &format!("{:<1$}", fit(&self.status, width as usize), width as usize));
+        attroff(A_REVERSE());
+        mvaddstr(height - 1, 0, &fit(SHORTCUTS, width as usize));
+        refresh();
+    }
+
+    /// Draws the search line and the list of services, scrolled to the selected one
+    fn draw_services(&mut self, y: i32, x: i32, height: i32, width: i32) {
+        frame(y, x, height, width, "Services", self.focus != Focus::Recipe);
+        let inner = (width - 2) as usize;
+        let prompt = &crate::config().prompt.value;
+        if self.focus == Focus::Search {
+            attron(A_BOLD());
+        }
+        mvaddstr(y + 1, x + 1, &fit(&format!("{}{}", prompt, self.search), inner));
+        attroff(A_BOLD());
+
+        let rows = (height - 3).max(0) as usize;
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if rows > 0 && self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+        let mut lines: Vec<String> = self.visible.iter()
+            .map(|index| {
+                let entry = &self.entries[*index];
+                format!("{}{}", if entry.saved { "  " } else { "* " }, entry.record.title)
+            })
+            .collect();
+        if let Some(title) = self.new_title() {
+            lines.push(format!("+ add '{}'", title));
+        }
+        if lines.is_empty() {
+            mvaddstr(y + 2, x + 1, &fit("No services yet, press / to add one", inner));
+        }
+        for (row, line) in lines.iter().enumerate().skip(self.scroll).take(rows) {
+            if row == self.selected {
+                attron(A_REVERSE());
+            }
+            mvaddstr(y + 2 + (row - self.scroll) as i32, x + 1, &format!("{:<1$}", fit(line, inner), inner));
+            if row == self.selected {
+                attroff(A_REVERSE());
+            }
+        }
+        // where the selection is once the list no longer fits
+        if lines.len() > rows {
+            let position = format!(" {}/{} ", (self.selected + 1).min(lines.len()), lines.len());
+            mvaddstr(y + height - 1, x + width - 2 - position.len() as i32, &position);
+        }
+    }
+
+    /// Draws the recipe of the selected service
+    fn draw_recipe(&self, y: i32, x: i32, height: i32, width: i32) {
+        frame(y, x, height, width, "Recipe", self.focus == Focus::Recipe);
+        let inner = (width - 2) as usize;
+        let Some(index) = self.current() else {
+            mvaddstr(y + 1, x + 1, &fit("No service selected", inner));
+            return;
+        };
+        let entry = &self.entries[index];
+        attron(A_BOLD());
+        mvaddstr(y + 1, x + 1, &fit(&entry.record.title, inner));
+        attroff(A_BOLD());
+        if !entry.saved {
+            mvaddstr(y + 2, x + 1, &fit("* not remembered until a password is generated", inner));
+        }
+        let aliases = self.aliases_of(&entry.record.title);
+        if !aliases.is_empty() {
+            mvaddstr(y + 3, x + 1, &fit(&format!("also called {}", aliases.join(", ")), inner));
+        }
+        for (row, field) in self.fields().into_iter().enumerate().take((height - 6).max(0) as usize) {
+            let (label, value) = field_text(&entry.record, field);
+            let line = format!("{:<20} {}", label, value);
+            let selected = self.focus == Focus::Recipe && row == self.field;
+            if selected {
+                attron(A_REVERSE());
+            }
+            mvaddstr(y + 5 + row as i32, x + 1, &fit(&line, inner));
+            if selected {
+                attroff(A_REVERSE());
+            }
+        }
+    }
+}
+
+/// Cuts the text to the width of the screen
+fn fit(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// Draws a box with the title in its top border
+fn frame(y: i32, x: i32, height: i32, width: i32, title: &str, focused: bool) {
+    mvhline(y, x + 1, ACS_HLINE(), width - 2);
+    mvhline(y + height - 1, x + 1, ACS_HLINE(), width - 2);
+    mvvline(y + 1, x, ACS_VLINE(), height - 2);
+    mvvline(y + 1, x + width - 1, ACS_VLINE(), height - 2);
+    mvaddch(y, x, ACS_ULCORNER());
+    mvaddch(y, x + width - 1, ACS_URCORNER());
+    mvaddch(y + height - 1, x, ACS_LLCORNER());
+    mvaddch(y + height - 1, x + width - 1, ACS_LRCORNER());
+    if focused {
+        attron(A_BOLD());
+    }
+    mvaddstr(y, x + 2, &format!(" {} ", title));
+    attroff(A_BOLD());
+}
+
+/// The label and the value of a line of the recipe pane
+fn field_text(record: &ServiceRecord, field: Field) -> (&'static str, String) {
+    let spec = &record.spec;
+    let yes_no = |value: bool| if value { "yes" } else { "no" }.to_string();
+    let passphrase = match spec.mode {
+        Mode::Passphrase(passphrase) => passphrase,
+        _ => PassphraseSpec::default()
+    };
+    match field {
+        Field::Number => ("Password number", record.pass_num.to_string()),
+        Field::Mode => ("Mode", match spec.mode {
+            Mode::Password => "password",
+            Mode::Passphrase(_) => "passphrase",
+            Mode::Pin => "pin"
+        }.to_string()),
+        Field::Length => (match spec.mode {
+            Mode::Password => "Length",
+            Mode::Passphrase(_) => "Words",
+            Mode::Pin => "Digits"
+        }, spec.length.to_string()),
+        Field::Classes => ("Classes", spec.key.to_classes()),
+        Field::Symbols => ("Symbols", spec.charset.symbols()),
+        Field::Excluded => ("Excluded", none_if_empty(spec.charset.excluded())),
+        Field::Lookalikes => ("No look-alikes", yes_no(spec.charset.no_lookalikes)),
+        Field::Rules => ("Rules", none_if_empty(spec.rules.to_string())),
+        Field::Separator => ("Separator", passphrase.separator.map_or("none".to_string(), |c| c.to_string())),
+        Field::Capitalize => ("Capitalize", yes_no(passphrase.capitalize)),
+        Field::Digit => ("Digit", yes_no(passphrase.digit)),
+        Field::Algorithm => ("Algorithm", spec.algorithm.number().to_string()),
+        Field::Notes => ("Notes", none_if_empty(record.notes.clone())),
+        Field::Tags => ("Tags", none_if_empty(record.tags.join(", ")))
+    }
+}
+
+fn none_if_empty(value: String) -> String {
+    if value.is_empty() { "none".to_string() } else { value }
+}
+
+/// Changes a line of the recipe to the typed value
+/// ### Returns
+/// Why the value is invalid
+fn set_field(record: &mut ServiceRecord, field: Field, value: &str) -> Result<(), String> {
+    let spec = &mut record.spec;
+    let number = |value: &str| value.parse::<u8>().map_err(|_| format!("'{}' is not a number from 0 to 255", value));
+    match field {
+        Field::Number => {
+            record.pass_num = number(value)?.max(1);
+        },
+        Field::Mode => {
+            let mode = match (value.to_ascii_lowercase().as_str(), spec.mode) {
+                ("password", _) => Mode::Password,
+                ("pin", _) => Mode::Pin,
+                ("passphrase", Mode::Passphrase(passphrase)) => Mode::Passphrase(passphrase),
+                ("passphrase", _) => Mode::Passphrase(PassphraseSpec::default()),
+                _ => return Err(format!("unknown mode '{}'", value))
+            };
+            spec.set_mode(mode);
+        },
+        Field::Length => spec.length = number(value)?,
+        Field::Classes => spec.key = Key::from_classes(value).map_err(|err| err.to_string())?,
+        Field::Symbols => {
+            let symbols = if value == "all" { project_stella::Charset::default().symbols() } else { value.to_string() };
+            spec.charset.set_symbols(&symbols).map_err(|err| err.to_string())?;
+        },
+        Field::Excluded => {
+            spec.charset.set_excluded(if value == "none" { "" } else { value }).map_err(|err| err.to_string())?;
+        },
+        Field::Rules => {
+            spec.rules = match value {
+                "" | "none" => Rules::default(),
+                rules => Rules::parse(rules).map_err(|err| err.to_string())?
+            };
+        },
+        Field::Separator => {
+            if let Mode::Passphrase(passphrase) = &mut spec.mode {
+                let mut chars = value.chars();
+                passphrase.separator = match (value, chars.next(), chars.next()) {
+                    ("none", _, _) => None,
+                    (_, Some(c), None) => Some(c),
+                    _ => return Err("the separator must be a single character or 'none'".to_string())
+                };
+            }
+        },
+        Field::Algorithm => {
+            spec.algorithm = Algorithm::from_number(number(value)?).map_err(|err| err.to_string())?;
+        },
+        Field::Notes => record.notes = value.to_string(),
+        Field::Tags => record.tags = service_db_actions::parse_tags(value),
+        Field::Lookalikes | Field::Capitalize | Field::Digit => ()
+    }
+    Ok(())
+}
+
+/// The lines of the help overlay
+fn help_lines() -> Vec<String> {
+    [
+        "Up/Down, PgUp/PgDn  choose a service",
+        "/                   search by title or alias, Enter selects the service or adds it as a new one",
+        "Enter or g          generate the password and copy it to the clipboard",
+        "v                   show the password on screen, also in groups for reading it aloud",
+        "r                   show the password as a QR code to scan with a phone",
+        "+ and -             change the password number, e.g. after the password leaked",
+        "e                   edit the recipe: length, characters, rules, mode and algorithm",
+        "n                   rename the service, its password stays the same",
+        "d                   delete the service and its aliases",
+        "a                   edit the aliases of the service, other names sharing its password",
+        "m                   merge the service into another one, it becomes an alias of it",
+        "s                   unlock a session: enter the master password once for all",
+        "                    services using algorithm version 3 until idle for too long",
+        "l                   lock the session",
+        "p                   change the master password and walk through every service",
+        "c                   show the settings in effect",
+        "q                   quit",
+        "",
+        "A * marks services whose recipe is remembered once a password is generated."
+    ].iter().map(|line| line.to_string()).collect()
+}
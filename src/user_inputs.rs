@@ -1,7 +1,10 @@
 use std::io::Write;
+use rusqlite::Connection;
 use zeroize::Zeroize;
-use crate::{service_cli, generator, characters::Key, auth::auth_user};
-use arboard::Clipboard;
+use project_stella::{auth, password_change, Generator, Mode};
+use project_stella::{display, service_db_actions};
+use crate::reveal;
+use crate::session::Session;
 
 /// Takes in input from stdin in terminal with the given prompt
 /// ### Params
@@ -35,168 +38,254 @@ pub fn get_visible_input_prompt(prompt: &str) -> String {
 pub fn get_hidden_input_prompt(prompt: &str) -> String {
     print!("{}", prompt);
     std::io::stdout().flush().unwrap();
-    let hidden_input = rpassword::read_password().unwrap();
-    return hidden_input
+    rpassword::read_password().unwrap()
 }
 
-/// Gets service input from the user
-/// ### Returns:
-/// Option<String> which is None if the user input is empty string
+/// Opens the authentication database
 /// ### Side-effect
-/// Prints curses interface, takes user input and reads/writes database entries
-fn get_service() -> Option<String> {
-    get_visible_input_prompt("Press Enter and then input service or optionally service {space} password number.");
-    let service = service_cli::create_service_screen();
-    
-    match service.as_str() {
-        "" => None,
-        _ => Some(service),
-    }
+/// Exits the process with a message if the database cannot be opened or was written by a newer build
+pub fn auth_connection() -> Connection {
+    auth::get_connection(crate::paths().auth_db()).unwrap_or_else(|err| {
+        println!("Failed to open the user database: {}", err);
+        std::process::exit(1);
+    })
 }
 
-/// Gets password property i.e. password legal character-types from the user input
-/// ### Returns:
-/// A Key object which will return to default Key if the user input fails
-/// ### Side-effect:
-/// Reads from stdin
-fn get_key() -> Key {
-    print!("Input key in format TFTF where T is true and F is false. ");
-    println!("And the order is uppercase, lowercase, numbers, and symbols respectively");
-    let key_str = get_visible_input_prompt("> ");
-    if key_str.len() != 5 {
-        println!("Operation failed, number of input characters incorrect. Switching key to default.");
-        return Key::default();
-    }
+/// Opens the service records database
+/// ### Side-effect
+/// Exits the process with a message if the database cannot be opened or was written by a newer build
+pub fn services_connection() -> Connection {
+    service_db_actions::get_connection(crate::paths().services_db()).unwrap_or_else(|err| {
+        println!("Failed to open the services database: {}", err);
+        std::process::exit(1);
+    })
+}
 
-    // check for all false
-    if key_str.trim().to_lowercase() == "ffff" {
-        println!("Operation failed, all cannot be false. Switching key to default.");
-        return Key::default();
-    }
+/// Authenticates user based on the input username and password.
+/// New users are created after confirming their password.
+/// ### Parameters
+/// - `username` : username of person to authenticate
+/// - `password` : passsword of the person to authenticate
+/// ### Returns
+/// a boolean; true if the username and password match and false otherwise
+/// ### Side-effects
+/// Reads in password confirmation for new users from stdin.
+/// 
+/// Will zeroize the password if the password and confirm password do not match
+/// 
+/// Reads from and writes to authentication sqlite database.
+pub fn auth_user(username: &str, password: &mut str) -> bool {
+    let conn = auth_connection();
 
-    let mut bools: [bool; 4] = [true, true, true, true];
+    if auth::user_exists(&conn, username) {
+        return auth::verify_user(&conn, username, password);
+    }
 
-    for (index, c) in key_str.trim().chars().enumerate() {
-        match c {
-            't' | 'T' => bools[index] = true,
-            'f' | 'F' => bools[index] = false,
-            _ => {
-                println!("Encountered non-t or non-f character. Switching key to default.");
-                return Key::default();
-            }
-        }
+    let mut confirm_pass = get_hidden_input_prompt("Confirm password: ");
+    if confirm_pass != *password {
+        confirm_pass.zeroize();
+        password.zeroize();
+        return false;
     }
+    confirm_pass.zeroize();
 
-    Key::from_arr(bools)
+    let profile = crate::config().profile().expect("the profile is checked when the config is loaded");
+    auth::add_user_with_profile(&conn, username, password, &profile)
 }
 
-/// Gets length of the password to generate
+/// Asks a yes or no question
 /// ### Returns:
-/// A unsigned byte of user input length. If user input fails, default length 16 is returned.
+/// true if the user answered with y or yes
 /// ### Side-effect
-/// Reads from stdit
-fn get_len() -> u8 {
-    let len_str = get_visible_input_prompt("> ");
-    let len: u8 = len_str.trim().parse().unwrap_or_else(|_| {
-        println!("Failed to convert to number. Setting to default password length.");
-        return 16;
-    });
+/// Reads from stdin
+fn get_yes_no(prompt: &str) -> bool {
+    let answer = get_visible_input_prompt(prompt);
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
 
-    if len < 4 {
-        println!("Number too small. Password length must be >= 4. Setting default password length.");
-        return 16;
+/// Reads the KDF profile of the user
+/// ### Returns
+/// The generator with the user's Argon2 parameters or None if the stored profile is invalid
+/// ### Side-effect
+/// Reads from the authentication database, prints an error if the profile is invalid
+fn get_profile(username: &str) -> Option<Generator> {
+    let conn = auth_connection();
+    let profile = auth::read_profile(&conn, username);
+    if profile.is_none() {
+        println!("The stored KDF profile is invalid.");
     }
-
-    return len;
+    profile
 }
 
-/// Generates a password from given parameters and copies it to clipboard
+/// Changes the master password, then walks through the services so their passwords can be
+/// changed on the websites. A change left unfinished is continued instead.
 /// ### Params
 /// - `username`: name of logged in user
-/// - `service` : valid or invalid service as set by the user
-/// - `key`     : key containing desired properties of generated password
-/// - `len`     : length of the password to generate
-/// - `clipboard`: clipboard object to copy the generated password into
+/// - `session` : locked when the password changes since its master key belongs to the old password
 /// ### Side-effect
-/// Passes value to the system clipboard
-/// ### Panics
-/// No, but returns early if service is not set
-fn gen(username: &str, service: &Option<String>, key: Key, len: u8, clipboard: &mut Clipboard) {
-    if service.is_none() {
-        println!("Service is unset. Please set service first!");
-        return;
-    }
+/// Reads from stdin, writes to the authentication database, shows passwords in the overlay
+pub fn change_password(username: &str, session: &Session) {
+    let mut conn = auth_connection();
+    let mut old_password;
+    let mut new_password;
+    if password_change::is_pending(&conn, username) {
+        println!("Continuing the unfinished password change.");
+        old_password = get_hidden_input_prompt("Old password: ");
+        if !password_change::verify_old_password(&conn, username, &old_password) {
+            old_password.zeroize();
+            println!("Password did not match the old password. Try again.");
+            return;
+        }
+        new_password = get_hidden_input_prompt("Current password: ");
+        if !auth::verify_user(&conn, username, &new_password) {
+            old_password.zeroize();
+            new_password.zeroize();
+            println!("Password did not match login password. Try again.");
+            return;
+        }
+    } else {
+        old_password = get_hidden_input_prompt("Current password: ");
+        if !auth::verify_user(&conn, username, &old_password) {
+            old_password.zeroize();
+            println!("Password did not match login password. Try again.");
+            return;
+        }
+        new_password = get_hidden_input_prompt("New password: ");
+        let mut confirm_pass = get_hidden_input_prompt("Confirm new password: ");
+        let matches = confirm_pass == new_password;
+        confirm_pass.zeroize();
+        if !matches || new_password == old_password {
+            old_password.zeroize();
+            new_password.zeroize();
+            println!("{}", if matches { "The new password is the same as the current one." } else { "Passwords did not match." });
+            return;
+        }
 
-    let mut salt: String = String::new();
-    salt.push_str(&username);
-    salt.push_str(&service.as_ref().unwrap());
-    let mut password = get_hidden_input_prompt("Password: ");
-    if !auth_user(username, &mut password) {
-        println!("Password did not match login password. Try again.");
-        return;
+        let service_conn = services_connection();
+        let changed = match service_db_actions::read_all_rows(&service_conn, username) {
+            Ok(titles) => password_change::change_password(&mut conn, username, &old_password, &new_password, &titles),
+            Err(err) => {
+                println!("Failed to read the services: {}", err);
+                false
+            }
+        };
+        if !changed {
+            old_password.zeroize();
+            new_password.zeroize();
+            println!("Failed to change the password.");
+            return;
+        }
+        if session.lock() {
+            println!("Session locked, its master key belongs to the old password.");
+        }
+        println!("Password changed. Change the password of every service on its website now or later by pressing p.");
     }
-    let mut generated_pass = generator::generate_pass(&mut password, salt.as_bytes(), len, key);
-    
-    clipboard.set_text(generated_pass.to_string()).unwrap();
-    generated_pass.zeroize();
-    println!("Generated password and copied to clipboard!");
-}
 
-/// Prints out the help string 
-fn help() {
-    println!("Available commands:");
-    println!("'serv': Use this to set service for which the password is being generated, ex: Netflix");
-    print!("'key' : Use this to set combination of character types you want in the password,");
-    println!(" i.e. uppercase, lowercase, nums, symbols. Default: All characters legal");
-    println!("'len' : Use this to set length of password. Default: 16");
-    println!("'gen' : Use this to generate the password and copy to clipboard. You will be asked to authenticate!");
-    println!("'help': This command.");
-    println!("'print': Prints out the set values for all arguments");
-    println!("'exit': Exits program.")
+    migrate_services(&conn, username, &old_password, &new_password);
+    old_password.zeroize();
+    new_password.zeroize();
 }
 
-/// Prints all the user selections made that will influence the generated password
-/// ### Parameters
-/// - `username` : name of logged in user
-/// - `service`  : service as set by the user (can be None)
-/// - `key`      : Key containing set (or default) properties of generated password by user
-fn print(username: &str, service: &Option<String>, key: Key, len: u8) {
-    print!("Logged in as: {}", username);
-    let service_string = match service {
-        Some(s) => s,
-        None => "service not set" 
+/// Shows the old and new password of every pending service and asks whether it was changed.
+/// Runs while the full-screen interface is suspended, the passwords are shown in its overlay
+/// so they are wiped afterwards instead of left in the scrollback.
+/// ### Params
+/// - `conn`: connection to the authentication database
+/// - `username`: name of logged in user
+/// - `old_password`, `new_password`: the master passwords before and after the change
+/// ### Side-effect
+/// Reads from stdin, shows passwords on the n_curses screen, writes the progress to the authentication database
+fn migrate_services(conn: &Connection, username: &str, old_password: &str, new_password: &str) {
+    let profile = match get_profile(username) {
+        Some(profile) => profile,
+        None => return
+    };
+    let migrations = match password_change::read_migrations(conn, username) {
+        Ok(migrations) => migrations,
+        Err(err) => {
+            println!("Failed to read the pending services: {}", err);
+            return;
+        }
     };
-    println!("Service set as: {}", service_string);
-    println!("Generated password will contain: {}", key.to_str());
-    println!("Generated password length set to: {}", len);
+    let pending: Vec<_> = migrations.iter().filter(|migration| !migration.migrated).collect();
+    println!("{} of {} services migrated.", migrations.len() - pending.len(), migrations.len());
+
+    let service_conn = services_connection();
+    for (index, migration) in pending.iter().enumerate() {
+        let record = match service_db_actions::read_service(&service_conn, username, &migration.title) {
+            Some(record) => record,
+            None => {
+                // the service is gone, nothing left to change
+                password_change::mark_migrated(conn, username, &migration.title);
+                continue;
+            }
+        };
+        let service_salt = record.salt_part();
+        let generate = |password: &str| {
+            let mut password = password.to_string();
+            profile.generate(&mut password, &[username, service_salt.as_str()], &record.spec)
+        };
+        let (mut old_pass, mut new_pass) = match (generate(old_password), generate(new_password)) {
+            (Ok(old_pass), Ok(new_pass)) => (old_pass, new_pass),
+            (Err(err), _) | (_, Err(err)) => {
+                println!("Skipping {}, failed to generate password: {}", record.title, err);
+                continue;
+            }
+        };
+        let heading = format!("[{}/{}] {} (password number {})", index + 1, pending.len(), record.title, record.pass_num);
+        let mut lines = vec![format!("old: {}", old_pass), format!("new: {}", new_pass)];
+        if !matches!(record.spec.mode, Mode::Passphrase(_)) {
+            lines.push(String::new());
+            lines.push(format!("new: {}", display::grouped(&new_pass, display::GROUP_SIZE)));
+        }
+        old_pass.zeroize();
+        new_pass.zeroize();
+        println!("{}", heading);
+        show_on_screen(&heading, lines);
+
+        let answer = get_visible_input_prompt("Changed it on the website? (y)es, (s)kip for now, (q)uit: ");
+        match answer.trim() {
+            "y" | "yes" => password_change::mark_migrated(conn, username, &migration.title),
+            "q" | "quit" => break,
+            _ => ()
+        }
+    }
+
+    if password_change::finish(conn, username) {
+        println!("Every service uses the new password now.");
+        return;
+    }
+    let left = password_change::read_migrations(conn, username)
+        .map_or(0, |migrations| migrations.iter().filter(|migration| !migration.migrated).count());
+    println!("{} services still use the old password. Press p to continue.", left);
 }
 
-/// Starts the CLI for taking in password properties as set by the user and generating passwords
+/// Shows lines in the overlay of the suspended full-screen interface, then suspends it again
 /// ### Params
-/// - `username` : name of the logged in user
-/// - `clipboard`: clipboard object where the generated password will be delivered
+/// - `title`: first line of the overlay
+/// - `lines`: what to show, wiped from memory afterwards
+fn show_on_screen(title: &str, lines: Vec<String>) {
+    ncurses::reset_prog_mode();
+    reveal::show(title, lines);
+    ncurses::endwin();
+}
+
+/// Offers the logged in user the services saved before services belonged to users
 /// ### Side-effect
-/// Takes in user input and the various functions it calls may carry out database operations
-/// #### Panics
-/// No. But it will exit if the user asks for an exit
-pub fn start_user_input_loop(username: &str, clipboard: &mut Clipboard) {
-    let mut service = None;
-    let mut key = Key::default();
-    let mut len = 16;
-
-    loop {
-        let command = get_visible_input_prompt("> ");
-
-        match command.as_str() {
-            "serv\n"  => service = get_service(),
-            "key\n"   => key = get_key(),
-            "len\n"   => len = get_len(),
-            "gen\n"   => gen(username, &service, key, len, clipboard),
-            "help\n"  => help(),
-            "print\n" => print(username, &service, key, len),
-            "exit\n"  => std::process::exit(0),
-            _ => println!("Unknown command. Type 'help' to get list of valid commands.")
-        }
+/// Reads from stdin, writes to the services database
+pub fn assign_unowned_services(username: &str) {
+    let conn = services_connection();
+    let unowned = service_db_actions::count_unowned(&conn).unwrap_or(0);
+    if unowned == 0 {
+        return;
     }
-
-}
\ No newline at end of file
+    println!("{} services were saved before every user had their own services. If they are not yours,", unowned);
+    println!("the user they belong to can claim them when logging in.");
+    if !get_yes_no(format!("Are they yours, {}? [y/N] ", username).as_str()) {
+        return;
+    }
+    match service_db_actions::assign_unowned(&conn, username) {
+        Ok(assigned) => println!("{} services are yours now.", assigned),
+        Err(err) => println!("Failed to assign the services: {}", err)
+    }
+}
@@ -0,0 +1,67 @@
+//! Tests for the configuration file
+
+use std::path::{Path, PathBuf};
+use project_stella::{Config, Error, Generator, Key, Variant};
+use project_stella::config::{ClipboardBackend, Source};
+
+#[test]
+fn missing_values_keep_the_defaults() {
+    let config = Config::parse("", Path::new("config.toml")).unwrap();
+    assert_eq!(config, Config::default());
+    assert_eq!(config.length.source, Source::Default);
+    assert_eq!(config.profile().unwrap(), Generator::new());
+    assert_eq!(config.spec().length, 16);
+    assert_eq!(config.clipboard_timeout.value, 30);
+
+    let config = Config::load("/nonexistent/stellar/config.toml").unwrap();
+    assert_eq!(config, Config::default());
+}
+
+#[test]
+fn values_come_from_the_file() {
+    let file = Path::new("/home/alice/.config/stellar/config.toml");
+    let config = Config::parse(r#"
+        length = 24
+        classes = "uln"
+
+        [kdf]
+        memory = 8192
+        variant = "argon2i"
+
+        [clipboard]
+        timeout = 45
+        backend = "osc52"
+
+        [ui]
+        prompt = "stellar> "
+    "#, file).unwrap();
+
+    assert_eq!(config.length.value, 24);
+    assert_eq!(config.length.source, Source::File(PathBuf::from(file)));
+    assert_eq!(config.classes.value, Key::from_classes("uln").unwrap());
+    assert_eq!(config.kdf_iterations.source, Source::Default);
+    assert_eq!(config.profile().unwrap(), Generator::with_profile(8192, 4, 8, Variant::Argon2i).unwrap());
+    assert_eq!(config.clipboard_timeout.value, 45);
+    assert_eq!(config.clipboard_backend.value, ClipboardBackend::Osc52);
+    assert_eq!(config.prompt.value, "stellar> ");
+    assert_eq!(config.session_idle_minutes.source, Source::Default);
+
+    let spec = config.spec();
+    assert_eq!((spec.length, spec.key), (24, Key::from_classes("uln").unwrap()));
+}
+
+#[test]
+fn invalid_values_are_rejected() {
+    for contents in [
+        "length = 2",
+        "classes = \"x\"",
+        "colour = \"blue\"",
+        "[kdf]\nvariant = \"scrypt\"",
+        "[kdf]\nmemory = 1",
+        "[clipboard]\nbackend = \"x11\"",
+        "[ui]\nsession_idle_minutes = 0",
+        "length = "
+    ] {
+        assert!(matches!(Config::parse(contents, Path::new("config.toml")), Err(Error::Config(_))), "{}", contents);
+    }
+}
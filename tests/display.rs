@@ -0,0 +1,28 @@
+//! Tests for showing passwords on screen
+
+use project_stella::display::{self, GROUP_SIZE, VISIBLE_SPACE};
+use project_stella::Error;
+
+#[test]
+fn passwords_are_grouped() {
+    assert_eq!(display::grouped("abcdefghij", GROUP_SIZE), "abcd efgh ij");
+    assert_eq!(display::grouped("abcdefgh", GROUP_SIZE), "abcd efgh");
+    assert_eq!(display::grouped("abc", 0), "a b c");
+    assert_eq!(display::grouped("", GROUP_SIZE), "");
+    // spaces in the password cannot be mistaken for the separator
+    assert_eq!(display::grouped("ab cdef", GROUP_SIZE), format!("ab{}c def", VISIBLE_SPACE));
+}
+
+#[test]
+fn qr_codes_are_drawn_with_half_blocks() {
+    let code = display::qr_code("|%e]l`J1ntm\\h# 0").unwrap();
+    let lines: Vec<&str> = code.lines().collect();
+    let width = lines[0].chars().count();
+    // 16 bytes need version 2, 25 modules wide, plus a quiet zone of 4 on each side, two rows per line
+    assert_eq!(width, 33);
+    assert_eq!(lines.len(), 17);
+    assert!(lines.iter().all(|line| line.chars().count() == width));
+    assert!(code.chars().all(|c| matches!(c, ' ' | '\u{2580}' | '\u{2584}' | '\u{2588}' | '\n')));
+
+    assert!(matches!(display::qr_code(&"a".repeat(8000)), Err(Error::InvalidSpec(_))));
+}
@@ -0,0 +1,203 @@
+//! Tests for the generation algorithms
+
+use project_stella::{pin, Algorithm, Charset, Generator, Key, Mode, PassphraseSpec, PasswordSpec};
+
+fn generate(master: &str, spec: &PasswordSpec) -> String {
+    let mut master = master.to_string();
+    Generator::new().generate(&mut master, &["alice", "github1"], spec).unwrap()
+}
+
+#[test]
+fn v2_is_deterministic_and_differs_from_v1() {
+    let v1 = PasswordSpec::new(16, Key::default());
+    let mut v2 = v1;
+    v2.algorithm = Algorithm::V2;
+
+    assert_eq!(generate("master", &v2), generate("master", &v2));
+    assert_ne!(generate("master", &v1), generate("master", &v2));
+    assert_ne!(generate("master", &v2), generate("other master", &v2));
+}
+
+#[test]
+fn v2_honours_length_and_classes() {
+    for classes in ["u", "n", "ln", "us", "ulns"] {
+        let mut spec = PasswordSpec::new(24, Key::from_classes(classes).unwrap());
+        spec.algorithm = Algorithm::V2;
+        let pass = generate("master", &spec);
+
+        assert_eq!(pass.chars().count(), 24);
+        assert_eq!(pass.chars().any(|c| c.is_ascii_uppercase()), spec.key.upper, "{}", pass);
+        assert_eq!(pass.chars().any(|c| c.is_ascii_lowercase()), spec.key.lower, "{}", pass);
+        assert_eq!(pass.chars().any(|c| c.is_ascii_digit()), spec.key.num, "{}", pass);
+        assert_eq!(pass.chars().any(|c| !c.is_ascii_alphanumeric()), spec.key.sym, "{}", pass);
+    }
+}
+
+#[test]
+fn algorithm_numbers_round_trip() {
+    for algorithm in [Algorithm::V1, Algorithm::V2] {
+        assert_eq!(Algorithm::from_number(algorithm.number()).unwrap(), algorithm);
+    }
+    assert!(Algorithm::from_number(0).is_err());
+}
+
+fn passphrase(words: u8, separator: Option<char>, capitalize: bool, digit: bool) -> String {
+    let spec = PasswordSpec::passphrase(words, PassphraseSpec { separator, capitalize, digit });
+    generate("master", &spec)
+}
+
+#[test]
+fn passphrase_formatting_keeps_the_words() {
+    let plain = passphrase(6, Some(' '), false, false);
+    let words: Vec<&str> = plain.split(' ').collect();
+    assert_eq!(words.len(), 6);
+    assert!(words.iter().all(|word| word.chars().all(|c| c.is_ascii_lowercase() || c == '-')), "{}", plain);
+
+    assert_eq!(passphrase(6, Some('-'), false, false), words.join("-"));
+    assert_eq!(passphrase(6, None, false, false), words.concat());
+
+    let capitalized = passphrase(6, Some(' '), true, false);
+    assert_eq!(capitalized.to_lowercase(), plain);
+    assert!(capitalized.split(' ').all(|word| word.starts_with(|c: char| c.is_ascii_uppercase())));
+
+    let with_digit = passphrase(6, Some(' '), false, true);
+    assert_eq!(with_digit.chars().filter(|c| c.is_ascii_digit()).count(), 1);
+    assert_eq!(with_digit.replace(|c: char| c.is_ascii_digit(), ""), plain);
+
+    assert_ne!(passphrase(7, Some(' '), false, false).split(' ').take(6).collect::<Vec<_>>(), words);
+}
+
+#[test]
+fn passphrase_is_frozen() {
+    let mut spec = PasswordSpec::passphrase(6, PassphraseSpec { separator: Some('-'), capitalize: true, digit: true });
+    spec.algorithm = Algorithm::V2;
+    assert_eq!(generate("master", &spec), "Divisible6-Bloating-Massager-Jailer-Footsie-Stapling");
+}
+
+#[test]
+fn passphrase_needs_v2() {
+    let mut spec = PasswordSpec::passphrase(6, PassphraseSpec::default());
+    spec.algorithm = Algorithm::V1;
+    let mut master = "master".to_string();
+    assert!(Generator::new().generate(&mut master, &["alice", "github1"], &spec).is_err());
+    assert!(master.is_empty() || master.bytes().all(|b| b == 0));
+}
+
+#[test]
+fn pins_are_digits_and_not_weak() {
+    for digits in [4, 6, 8] {
+        let pin = generate("master", &PasswordSpec::pin(digits));
+        assert_eq!(pin.len(), digits as usize);
+        assert!(pin.chars().all(|c| c.is_ascii_digit()), "{}", pin);
+        assert!(!pin::is_weak_pin(&pin), "{}", pin);
+    }
+    assert_ne!(generate("master", &PasswordSpec::pin(4)), generate("other master", &PasswordSpec::pin(4)));
+}
+
+#[test]
+fn weak_pins_are_detected() {
+    for weak in ["0000", "1111", "1212", "1234", "4321", "7890", "0987", "1990", "2013", "6969",
+                 "123123", "123456", "654321", "111111", "121212", "147258"] {
+        assert!(pin::is_weak_pin(weak), "{}", weak);
+    }
+    for fine in ["8273", "3091", "5824", "902716", "1357", "73829104"] {
+        assert!(!pin::is_weak_pin(fine), "{}", fine);
+    }
+    // not PINs at all
+    for other in ["", "abcd", "12 4", "1a2b", "19x0", "\u{0}\u{0}"] {
+        assert!(!pin::is_weak_pin(other), "{:?}", other);
+    }
+}
+
+#[test]
+fn pin_needs_v2() {
+    let mut spec = PasswordSpec::pin(4);
+    spec.algorithm = Algorithm::V1;
+    let mut master = "master".to_string();
+    assert!(Generator::new().generate(&mut master, &["alice", "github1"], &spec).is_err());
+    assert!(PasswordSpec::pin(3).validate().is_err());
+}
+
+#[test]
+fn switching_modes_resets_the_length() {
+    let mut spec = PasswordSpec::default();
+    spec.set_mode(Mode::Pin);
+    assert_eq!(spec.length, Mode::Pin.default_length());
+    assert_eq!(spec.algorithm, Algorithm::LATEST);
+    spec.length = 6;
+    spec.set_mode(Mode::Pin);
+    assert_eq!(spec.length, 6);
+    spec.set_mode(Mode::Password);
+    assert_eq!(spec.length, Mode::Password.default_length());
+}
+
+#[test]
+fn charset_limits_the_characters() {
+    for algorithm in [Algorithm::V1, Algorithm::V2] {
+        let mut spec = PasswordSpec::new(32, Key::default());
+        spec.algorithm = algorithm;
+        spec.charset.set_symbols("!#$%").unwrap();
+        spec.charset.set_excluded("abcXYZ").unwrap();
+        spec.charset.no_lookalikes = true;
+        let password = generate("master", &spec);
+
+        assert_eq!(password.chars().count(), 32);
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%".contains(c)), "{}", password);
+        assert!(!password.contains(|c| "abcXYZ0O1lI".contains(c)), "{}", password);
+        assert!(password.contains(|c: char| c.is_ascii_uppercase()), "{}", password);
+        assert!(password.contains(|c: char| c.is_ascii_lowercase()), "{}", password);
+        assert!(password.contains(|c: char| c.is_ascii_digit()), "{}", password);
+        assert!(password.contains(|c| "!#$%".contains(c)), "{}", password);
+    }
+}
+
+#[test]
+fn default_charset_keeps_passwords() {
+    let mut spec = PasswordSpec::new(16, Key::default());
+    spec.algorithm = Algorithm::V2;
+    let default = generate("master", &spec);
+    spec.charset.set_symbols(&Charset::default().symbols()).unwrap();
+    assert_eq!(generate("master", &spec), default);
+    spec.charset.set_excluded("\"").unwrap();
+    assert_ne!(generate("master", &spec), default);
+}
+
+#[test]
+fn charset_must_leave_enabled_types_a_character() {
+    let mut spec = PasswordSpec::new(16, Key::default());
+    spec.charset.set_symbols("").unwrap();
+    assert!(spec.validate().is_err());
+    spec.key.sym = false;
+    assert!(spec.validate().is_ok());
+    spec.charset.set_excluded("0123456789").unwrap();
+    assert!(spec.validate().is_err());
+    assert!(spec.charset.set_symbols("a").is_err());
+    assert!(spec.charset.set_excluded("é").is_err());
+}
+
+#[test]
+fn master_key_gives_the_same_passwords() {
+    let generator = Generator::new();
+    let mut master = "master".to_string();
+    let key = generator.master_key(&mut master, "alice").unwrap();
+    assert!(master.bytes().all(|b| b == 0));
+
+    let mut specs = vec![PasswordSpec::pin(6), PasswordSpec::passphrase(5, PassphraseSpec::default())];
+    let mut password = PasswordSpec { algorithm: Algorithm::V3, ..PasswordSpec::default() };
+    password.charset.no_lookalikes = true;
+    specs.push(password);
+    for spec in specs {
+        assert_eq!(spec.algorithm, Algorithm::V3);
+        assert_eq!(key.generate(&["github1"], &spec).unwrap(), generate("master", &spec));
+        assert_ne!(key.generate(&["github2"], &spec).unwrap(), generate("master", &spec));
+    }
+
+    // other users get other keys from the same master password
+    let mut master = "master".to_string();
+    let other = generator.master_key(&mut master, "bob").unwrap();
+    assert_ne!(other.generate(&["github1"], &PasswordSpec::pin(6)).unwrap(), key.generate(&["github1"], &PasswordSpec::pin(6)).unwrap());
+
+    // the key cannot make passwords of the algorithms salted with the master password
+    assert!(key.generate(&["github1"], &PasswordSpec::default()).is_err());
+    assert_eq!(format!("{:?}", key), "MasterKey(..)");
+}
@@ -0,0 +1,74 @@
+//! Tests for changing the master password and tracking the services to migrate
+
+use project_stella::{auth, password_change};
+use project_stella::password_change::Migration;
+
+fn titles() -> Vec<String> {
+    vec!["netflix".to_string(), "github".to_string()]
+}
+
+#[test]
+fn password_change_replaces_the_password() {
+    let mut conn = auth::get_connection(":memory:").unwrap();
+    assert!(auth::add_user(&conn, "alice", "old"));
+
+    assert!(!password_change::change_password(&mut conn, "alice", "wrong", "new", &titles()));
+    assert!(!password_change::is_pending(&conn, "alice"));
+    assert!(auth::verify_user(&conn, "alice", "old"));
+
+    assert!(password_change::change_password(&mut conn, "alice", "old", "new", &titles()));
+    assert!(auth::verify_user(&conn, "alice", "new"));
+    assert!(!auth::verify_user(&conn, "alice", "old"));
+    assert!(password_change::verify_old_password(&conn, "alice", "old"));
+    assert!(!password_change::verify_old_password(&conn, "alice", "new"));
+    assert!(!password_change::is_pending(&conn, "bob"));
+}
+
+#[test]
+fn services_stay_pending_until_migrated() {
+    let mut conn = auth::get_connection(":memory:").unwrap();
+    assert!(auth::add_user(&conn, "alice", "old"));
+    assert!(password_change::change_password(&mut conn, "alice", "old", "new", &titles()));
+    // a second change has to wait for the first to finish
+    assert!(!password_change::change_password(&mut conn, "alice", "new", "newer", &titles()));
+
+    password_change::mark_migrated(&conn, "alice", "github");
+    assert_eq!(password_change::read_migrations(&conn, "alice").unwrap(), vec![
+        Migration { title: "github".to_string(), migrated: true },
+        Migration { title: "netflix".to_string(), migrated: false }
+    ]);
+    assert!(!password_change::finish(&conn, "alice"));
+    assert!(password_change::is_pending(&conn, "alice"));
+
+    password_change::mark_migrated(&conn, "alice", "netflix");
+    assert!(password_change::finish(&conn, "alice"));
+    assert!(!password_change::is_pending(&conn, "alice"));
+    assert!(!password_change::verify_old_password(&conn, "alice", "old"));
+    assert!(password_change::read_migrations(&conn, "alice").unwrap().is_empty());
+}
+
+#[test]
+fn password_change_keeps_the_profile() {
+    let mut conn = auth::get_connection(":memory:").unwrap();
+    let profile = project_stella::Generator::with_profile(8192, 2, 2, project_stella::Variant::Argon2i).unwrap();
+    assert!(auth::add_user_with_profile(&conn, "bob", "old", &profile));
+    assert!(password_change::change_password(&mut conn, "bob", "old", "new", &[]));
+    assert_eq!(auth::read_profile(&conn, "bob"), Some(profile));
+    assert!(password_change::finish(&conn, "bob"));
+}
+
+#[test]
+fn pending_services_follow_renames() {
+    let mut conn = auth::get_connection(":memory:").unwrap();
+    assert!(auth::add_user(&conn, "alice", "old"));
+    let titles = vec!["gmial".to_string(), "gmail.com".to_string(), "github".to_string()];
+    assert!(password_change::change_password(&mut conn, "alice", "old", "new", &titles));
+
+    password_change::rename_service(&conn, "alice", "gmial", "gmail").unwrap();
+    // a merged duplicate leaves the service it was merged into
+    password_change::rename_service(&conn, "alice", "gmail.com", "gmail").unwrap();
+    assert_eq!(password_change::read_migrations(&conn, "alice").unwrap(), vec![
+        Migration { title: "github".to_string(), migrated: false },
+        Migration { title: "gmail".to_string(), migrated: false }
+    ]);
+}
@@ -0,0 +1,107 @@
+//! Tests for the data and configuration directories
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use project_stella::{auth, service_db_actions, Paths};
+
+/// Finds the directories with only the given environment variables set
+fn resolve(data_dir: Option<&str>, vars: &[(&str, &str)]) -> project_stella::Result<Paths> {
+    resolve_configured(data_dir, None, vars)
+}
+
+/// Finds the directories like `resolve()` with a `data_dir` in the configuration file
+fn resolve_configured(data_dir: Option<&str>, configured: Option<&str>, vars: &[(&str, &str)]) -> project_stella::Result<Paths> {
+    let vars: HashMap<String, PathBuf> = vars.iter().map(|(name, value)| (name.to_string(), PathBuf::from(value))).collect();
+    Paths::from_env(data_dir.map(Path::new), configured.map(Path::new), |name| vars.get(name).cloned())
+}
+
+#[test]
+fn directories_follow_xdg() {
+    let paths = resolve(None, &[("HOME", "/home/alice")]).unwrap();
+    assert_eq!(paths.data_dir, PathBuf::from("/home/alice/.local/share/stellar"));
+    assert_eq!(paths.config_dir, PathBuf::from("/home/alice/.config/stellar"));
+    assert_eq!(paths.auth_db(), PathBuf::from("/home/alice/.local/share/stellar/auth.db"));
+    assert_eq!(paths.presets(), PathBuf::from("/home/alice/.config/stellar/presets.toml"));
+
+    let paths = resolve(None, &[("HOME", "/home/alice"), ("XDG_DATA_HOME", "/data"), ("XDG_CONFIG_HOME", "/config")]).unwrap();
+    assert_eq!(paths.services_db(), PathBuf::from("/data/stellar/service_records.db"));
+    assert_eq!(paths.config_dir, PathBuf::from("/config/stellar"));
+
+    // empty variables count as unset
+    let paths = resolve(None, &[("HOME", "/home/alice"), ("XDG_DATA_HOME", "")]).unwrap();
+    assert_eq!(paths.data_dir, PathBuf::from("/home/alice/.local/share/stellar"));
+
+    assert!(resolve(None, &[]).is_err());
+}
+
+#[test]
+fn data_dir_can_be_overridden() {
+    let vars = [("HOME", "/home/alice"), ("XDG_DATA_HOME", "/data"), ("STELLAR_HOME", "/stellar")];
+    let paths = resolve(None, &vars).unwrap();
+    assert_eq!(paths.data_dir, PathBuf::from("/stellar"));
+    assert_eq!(paths.config_dir, PathBuf::from("/home/alice/.config/stellar"));
+
+    let paths = resolve(Some("/flag"), &vars).unwrap();
+    assert_eq!(paths.data_dir, PathBuf::from("/flag"));
+
+    // the configuration file is overridden by the environment
+    let paths = resolve_configured(None, Some("/configured"), &vars).unwrap();
+    assert_eq!(paths.data_dir, PathBuf::from("/stellar"));
+    let paths = resolve_configured(None, Some("/configured"), &vars[..2]).unwrap();
+    assert_eq!(paths.data_dir, PathBuf::from("/configured"));
+    assert_eq!(paths.config_file(), PathBuf::from("/home/alice/.config/stellar/config.toml"));
+}
+
+#[test]
+fn legacy_files_are_moved_once() {
+    let root = std::env::temp_dir().join(format!("stellar-paths-{}", std::process::id()));
+    let cwd = root.join("cwd");
+    std::fs::create_dir_all(&cwd).unwrap();
+    let conn = auth::get_connection(cwd.join("auth.db")).unwrap();
+    assert!(auth::add_user(&conn, "alice", "password"));
+    drop(conn);
+    std::fs::write(cwd.join("auth.db-journal"), "").unwrap();
+    // files of other programs are not taken
+    std::fs::write(cwd.join("presets.toml"), "").unwrap();
+    rusqlite::Connection::open(cwd.join("service_records.db")).unwrap()
+        .execute("CREATE TABLE other(id INTEGER)", ()).unwrap();
+
+    let paths = Paths { data_dir: root.join("data"), config_dir: root.join("config") };
+    paths.create().unwrap();
+    let (moved, left) = paths.move_legacy_files(&cwd).unwrap();
+    assert_eq!(moved, vec![paths.auth_db()]);
+    assert!(left.is_empty());
+    assert!(!cwd.join("auth.db").exists());
+    assert!(!cwd.join("auth.db-journal").exists());
+    assert!(root.join("data").join("auth.db-journal").exists());
+    assert!(cwd.join("service_records.db").exists());
+    assert!(cwd.join("presets.toml").exists());
+    assert!(!paths.presets().exists());
+    let conn = auth::get_connection(paths.auth_db()).unwrap();
+    assert!(auth::verify_user(&conn, "alice", "password"));
+    drop(conn);
+
+    // later starts leave the working directory alone
+    std::fs::remove_file(cwd.join("service_records.db")).unwrap();
+    service_db_actions::get_connection(cwd.join("service_records.db")).unwrap();
+    let (moved, left) = paths.move_legacy_files(&cwd).unwrap();
+    assert!(moved.is_empty() && left.is_empty());
+    assert!(cwd.join("service_records.db").exists());
+
+    // databases that would replace existing ones stay where they are
+    let fresh = Paths { data_dir: root.join("fresh"), config_dir: root.join("config") };
+    fresh.create().unwrap();
+    service_db_actions::get_connection(fresh.services_db()).unwrap();
+    let (moved, left) = fresh.move_legacy_files(&cwd).unwrap();
+    assert!(moved.is_empty());
+    assert_eq!(left, vec![cwd.join("service_records.db")]);
+
+    // nothing happens when the working directory is the data directory
+    let same = Paths { data_dir: root.join("same"), config_dir: root.join("config") };
+    same.create().unwrap();
+    service_db_actions::get_connection(same.services_db()).unwrap();
+    let (moved, left) = same.move_legacy_files(&same.data_dir).unwrap();
+    assert!(moved.is_empty() && left.is_empty());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,61 @@
+//! Tests for the site presets
+
+use project_stella::{Algorithm, Key, Mode, PassphraseSpec, PasswordSpec};
+use project_stella::presets::{self, Presets};
+
+#[test]
+fn bundled_presets_make_valid_specs() {
+    let presets = Presets::bundled();
+    assert!(presets.iter().count() > 0);
+    for preset in presets.iter() {
+        let mut spec = PasswordSpec { algorithm: Algorithm::LATEST, ..PasswordSpec::default() };
+        assert!(preset.apply(&mut spec).is_ok(), "{:?}", preset.names);
+    }
+}
+
+#[test]
+fn titles_and_urls_find_presets() {
+    let presets = Presets::bundled();
+    for title in ["paypal", "PayPal", "paypal.com", "https://www.paypal.com/signin", " www.paypal.com "] {
+        assert!(presets.find(title).is_some_and(|preset| preset.names.contains(&"paypal".to_string())), "{}", title);
+    }
+    assert!(presets.find("paypalx").is_none());
+    assert_eq!(presets::normalize("HTTP://WWW.Example.com/a/b"), "example.com");
+}
+
+#[test]
+fn applying_fits_the_length_and_characters() {
+    let preset = Presets::bundled().find("paypal").unwrap().clone();
+    let mut spec = PasswordSpec::passphrase(6, PassphraseSpec::default());
+    preset.apply(&mut spec).unwrap();
+    assert_eq!(spec.mode, Mode::Password);
+    assert_eq!(spec.length, 16);
+
+    let mut spec = PasswordSpec { length: 64, algorithm: Algorithm::LATEST, ..PasswordSpec::default() };
+    preset.apply(&mut spec).unwrap();
+    assert_eq!(Some(spec.length), preset.max_length);
+    assert_eq!(spec.key, Key::default());
+    assert_eq!(spec.charset.symbols(), "!#$%&()*@^");
+}
+
+#[test]
+fn user_presets_come_first() {
+    let dir = std::env::temp_dir().join(format!("stellar-presets-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("presets.toml");
+    let path = path.to_str().unwrap();
+
+    // missing file
+    assert!(Presets::load(path).unwrap().find("paypal").is_some());
+
+    std::fs::write(path, "[[preset]]\nnames = [\"paypal\", \"intranet\"]\nmax_length = 12\nclasses = \"ln\"\n").unwrap();
+    let presets = Presets::load(path).unwrap();
+    assert_eq!(presets.find("paypal.com").unwrap().max_length, Some(20));
+    assert_eq!(presets.find("paypal").unwrap().max_length, Some(12));
+    assert_eq!(presets.find("intranet").unwrap().classes.as_deref(), Some("ln"));
+
+    std::fs::write(path, "[[preset]]\nnames = [\"x\"]\nmax_len = 12\n").unwrap();
+    assert!(Presets::load(path).is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,63 @@
+//! Tests for the per-user KDF profiles
+
+use rusqlite::{params, Connection};
+use project_stella::{auth, Generator, PasswordSpec, Variant};
+
+#[test]
+fn profiles_round_trip() {
+    let conn = auth::get_connection(":memory:").unwrap();
+    let profile = Generator::with_profile(8192, 2, 2, Variant::Argon2i).unwrap();
+    assert!(auth::add_user_with_profile(&conn, "bob", "bob password", &profile));
+    assert!(auth::add_user(&conn, "alice", "alice password"));
+
+    assert_eq!(auth::read_profile(&conn, "bob"), Some(profile));
+    assert_eq!(auth::read_profile(&conn, "alice"), Some(Generator::new()));
+    assert_eq!(auth::read_profile(&conn, "carol"), None);
+    assert!(auth::verify_user(&conn, "bob", "bob password"));
+    assert!(!auth::verify_user(&conn, "bob", "alice password"));
+}
+
+#[test]
+fn users_from_before_profiles_keep_verifying() {
+    // the hash as stored by builds without profiles
+    let salt = "abcdefghijklmnop";
+    let hash = argon2::hash_encoded(b"old password", salt.as_bytes(), &argon2::Config::default()).unwrap();
+
+    let dir = std::env::temp_dir().join(format!("stellar-profiles-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("auth.db");
+    let legacy = Connection::open(&path).unwrap();
+    legacy.execute("CREATE TABLE auth(username TEXT NOT NULL UNIQUE, password_hash TEXT, password_salt TEXT)", ()).unwrap();
+    legacy.execute("INSERT INTO auth VALUES (?1, ?2, ?3)", params!["old", hash, salt]).unwrap();
+    drop(legacy);
+
+    // opening adds the profile columns with the parameters passwords were made with
+    let conn = auth::get_connection(path.to_str().unwrap()).unwrap();
+    assert!(auth::verify_user(&conn, "old", "old password"));
+    assert!(!auth::verify_user(&conn, "old", "new password"));
+    assert_eq!(auth::read_profile(&conn, "old"), Some(Generator::new()));
+
+    drop(conn);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn profile_changes_the_password() {
+    let spec = PasswordSpec::default();
+    let generate = |generator: Generator| {
+        let mut master = "master".to_string();
+        generator.generate(&mut master, &["alice", "github1"], &spec).unwrap()
+    };
+    let default = generate(Generator::new());
+    assert_eq!(generate(Generator::with_profile(16384, 4, 8, Variant::Argon2id).unwrap()), default);
+    assert_ne!(generate(Generator::with_profile(16384, 3, 8, Variant::Argon2id).unwrap()), default);
+    assert_ne!(generate(Generator::with_profile(16384, 4, 8, Variant::Argon2d).unwrap()), default);
+}
+
+#[test]
+fn invalid_profiles_are_rejected() {
+    assert!(Generator::with_profile(8192, 0, 1, Variant::Argon2id).is_err());
+    assert!(Generator::with_profile(8192, 1, 0, Variant::Argon2id).is_err());
+    assert!(Generator::with_profile(15, 1, 2, Variant::Argon2id).is_err());
+    assert!(Generator::with_profile(16, 1, 2, Variant::Argon2id).is_ok());
+}
@@ -0,0 +1,85 @@
+//! Tests for password rules
+
+use project_stella::{Algorithm, Charset, Error, Generator, Key, PasswordSpec, Rules};
+
+fn generate(spec: &PasswordSpec) -> String {
+    let mut master = "master".to_string();
+    Generator::new().generate(&mut master, &["alice", "github1"], spec).unwrap()
+}
+
+fn spec_with_rules(length: u8, classes: &str, rules: &str) -> PasswordSpec {
+    let mut spec = PasswordSpec::new(length, Key::from_classes(classes).unwrap());
+    spec.algorithm = Algorithm::LATEST;
+    spec.rules = Rules::parse(rules).unwrap();
+    spec
+}
+
+#[test]
+fn rules_round_trip_through_text() {
+    for text in ["", "n>=2,s>=2", "u>=1,u<=3,l<=10,repeat<=2,letter-first", "s<=0"] {
+        assert_eq!(Rules::parse(text).unwrap().to_string(), text);
+    }
+    assert_eq!(Rules::parse(" s>=2 , n>=3 ").unwrap().to_string(), "n>=3,s>=2");
+    for invalid in ["n>2", "x>=1", "repeat>=2", "n>=", "n>=300", "letter", "nn>=1"] {
+        assert!(Rules::parse(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn check_counts_types_and_repeats() {
+    let rules = Rules::parse("n>=2,s>=2,s<=3,repeat<=2,letter-first").unwrap();
+    assert!(rules.check("ab12!#xx"));
+    assert!(!rules.check("1ab2!#xx"));
+    assert!(!rules.check("ab1c!#xx"));
+    assert!(!rules.check("ab12!#x$%"));
+    assert!(!rules.check("ab12!#xxx"));
+}
+
+#[test]
+fn generated_passwords_follow_the_rules() {
+    for rules in ["n>=3,s>=3", "u<=1,l<=1,repeat<=1", "letter-first,s>=4"] {
+        let spec = spec_with_rules(12, "ulns", rules);
+        let password = generate(&spec);
+        assert_eq!(password.len(), 12);
+        assert!(spec.rules.check(&password), "{} {}", rules, password);
+        assert_eq!(generate(&spec), password);
+    }
+}
+
+#[test]
+fn impossible_rules_are_rejected() {
+    for (length, classes, rules) in [(8, "uln", "s>=1"), (8, "ulns", "n>=5,s>=4"), (8, "ln", "l<=3,n<=4"),
+                                     (8, "ulns", "n>=3,n<=2"), (8, "n", "letter-first"), (8, "ulns", "repeat<=0"),
+                                     (8, "ulns", "u<=0")] {
+        assert!(spec_with_rules(length, classes, rules).validate().is_err(), "{} {}", classes, rules);
+    }
+    assert!(spec_with_rules(8, "ulns", "n>=2,s>=2").validate().is_ok());
+
+    let mut v1 = spec_with_rules(16, "ulns", "n>=2");
+    v1.algorithm = Algorithm::V1;
+    assert!(v1.validate().is_err());
+}
+
+#[test]
+fn rules_are_checked_against_the_characters_left() {
+    // a single digit is left, three of them cannot be kept apart by one letter
+    let mut spec = spec_with_rules(4, "ln", "n>=3,repeat<=1");
+    spec.charset = Charset::default();
+    spec.charset.set_excluded("012345678").unwrap();
+    assert!(spec.validate().is_err());
+    spec.rules = Rules::parse("n>=2,repeat<=1").unwrap();
+    assert!(spec.validate().is_ok());
+    assert!(spec.rules.check(&generate(&spec)));
+
+    // letters limited to one leave three digits with a single letter between them
+    spec.rules = Rules::parse("l<=1,repeat<=1").unwrap();
+    assert!(spec.validate().is_err());
+}
+
+#[test]
+fn rules_too_hard_to_follow_give_up() {
+    let spec = spec_with_rules(20, "ulns", "n>=17");
+    assert!(spec.validate().is_ok());
+    let mut master = "master".to_string();
+    assert!(matches!(Generator::new().generate(&mut master, &["alice", "github1"], &spec), Err(Error::InvalidSpec(_))));
+}
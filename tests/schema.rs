@@ -0,0 +1,135 @@
+//! Tests for the schema versions of the databases
+
+use rusqlite::Connection;
+use project_stella::{auth, schema, Error};
+use project_stella::service_db_actions::{self, ServiceRecord};
+
+/// Makes an empty directory for database files of a test
+fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("stellar-schema-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn new_databases_get_the_newest_version() {
+    let dir = test_dir("new");
+    let auth_path = dir.join("auth.db");
+    let services_path = dir.join("service_records.db");
+
+    let conn = auth::get_connection(auth_path.to_str().unwrap()).unwrap();
+    let auth_version = schema::version(&conn).unwrap();
+    assert!(auth_version >= 1);
+    assert!(auth::add_user(&conn, "alice", "password"));
+    drop(conn);
+
+    let conn = service_db_actions::get_connection(services_path.to_str().unwrap()).unwrap();
+    let services_version = schema::version(&conn).unwrap();
+    assert!(services_version >= 1);
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("github", 2)).unwrap();
+    drop(conn);
+
+    // opening again keeps the data and the version
+    let conn = auth::get_connection(auth_path.to_str().unwrap()).unwrap();
+    assert_eq!(schema::version(&conn).unwrap(), auth_version);
+    assert!(auth::verify_user(&conn, "alice", "password"));
+    let conn = service_db_actions::get_connection(services_path.to_str().unwrap()).unwrap();
+    assert_eq!(schema::version(&conn).unwrap(), services_version);
+    assert_eq!(service_db_actions::read_pass_num(&conn, "alice", "github"), 2);
+
+    drop(conn);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn databases_of_newer_builds_are_refused() {
+    let dir = test_dir("newer");
+    let auth_path = dir.join("auth.db");
+    let services_path = dir.join("service_records.db");
+    for path in [&auth_path, &services_path] {
+        let newer = Connection::open(path).unwrap();
+        newer.pragma_update(None, "user_version", 1000).unwrap();
+    }
+
+    assert!(matches!(auth::get_connection(auth_path.to_str().unwrap()), Err(Error::Database(_))));
+    assert!(matches!(service_db_actions::get_connection(services_path.to_str().unwrap()), Err(Error::Database(_))));
+
+    // nothing was written to them
+    let newer = Connection::open(&auth_path).unwrap();
+    assert_eq!(schema::version(&newer).unwrap(), 1000);
+    assert!(newer.prepare("SELECT username FROM auth").is_err());
+
+    drop(newer);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn titles_differing_in_case_survive_the_migration_to_version_4() {
+    let dir = test_dir("case");
+    let path = dir.join("service_records.db");
+
+    // schema version 3 compared titles and aliases with their case
+    let legacy = Connection::open(&path).unwrap();
+    legacy.execute_batch("CREATE TABLE services(username TEXT, title TEXT NOT NULL, pass_num INTEGER, algorithm INTEGER NOT NULL DEFAULT 1,
+            length INTEGER NOT NULL DEFAULT 16, classes TEXT NOT NULL DEFAULT 'ulns', mode TEXT NOT NULL DEFAULT 'password',
+            separator TEXT NOT NULL DEFAULT '-', capitalize INTEGER NOT NULL DEFAULT 0, digit INTEGER NOT NULL DEFAULT 0,
+            symbols TEXT, excluded TEXT NOT NULL DEFAULT '', no_lookalikes INTEGER NOT NULL DEFAULT 0,
+            rules TEXT NOT NULL DEFAULT '', last_used INTEGER, use_count INTEGER NOT NULL DEFAULT 0, salt_title TEXT,
+            UNIQUE(username, title));
+        CREATE TABLE aliases(username TEXT NOT NULL, alias TEXT NOT NULL, title TEXT NOT NULL, UNIQUE(username, alias));
+        INSERT INTO services (username, title, pass_num, salt_title) VALUES ('alice', 'gmail', 1, 'gmail');
+        INSERT INTO services (username, title, pass_num, salt_title) VALUES ('alice', 'Gmail', 2, 'gmial');
+        INSERT INTO services (username, title, pass_num, salt_title) VALUES ('bob', 'GMAIL', 3, 'gmail');
+        INSERT INTO aliases VALUES ('alice', 'mail', 'gmail');
+        INSERT INTO aliases VALUES ('alice', 'Mail', 'Gmail');
+        INSERT INTO aliases VALUES ('alice', 'google', 'Gmail');
+        PRAGMA user_version = 3;").unwrap();
+    drop(legacy);
+
+    let conn = service_db_actions::get_connection(path.to_str().unwrap()).unwrap();
+    assert_eq!(schema::version(&conn).unwrap(), 4);
+    assert_eq!(service_db_actions::read_all_rows(&conn, "alice").unwrap(), vec!["gmail".to_string(), "Gmail (2)".to_string()]);
+    let gmail = service_db_actions::read_service(&conn, "alice", "GMAIL").unwrap();
+    assert_eq!(gmail.salt_part(), ServiceRecord::new("gmail", 1).salt_part());
+    let renamed = service_db_actions::read_service(&conn, "alice", "gmail (2)").unwrap();
+    assert_eq!(renamed.salt_part(), ServiceRecord::new("gmial", 2).salt_part());
+    assert_eq!(service_db_actions::read_service(&conn, "bob", "gmail").unwrap().pass_num, 3);
+
+    // aliases follow their service and keep apart too
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "mail"), Some(gmail));
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "Mail (2)"), Some(renamed.clone()));
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "google"), Some(renamed));
+
+    drop(conn);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn shared_derivation_ids_are_numbered_in_the_migration_to_version_4() {
+    let dir = test_dir("derivation");
+    let path = dir.join("service_records.db");
+
+    // schema version 3 let a new service take the salt title a renamed one still used
+    service_db_actions::get_connection(&path).unwrap();
+    let legacy = Connection::open(&path).unwrap();
+    legacy.execute_batch("DROP TABLE services;
+        CREATE TABLE services(username TEXT, title TEXT NOT NULL, pass_num INTEGER, algorithm INTEGER NOT NULL DEFAULT 1,
+            length INTEGER NOT NULL DEFAULT 16, classes TEXT NOT NULL DEFAULT 'ulns', mode TEXT NOT NULL DEFAULT 'password',
+            separator TEXT NOT NULL DEFAULT '-', capitalize INTEGER NOT NULL DEFAULT 0, digit INTEGER NOT NULL DEFAULT 0,
+            symbols TEXT, excluded TEXT NOT NULL DEFAULT '', no_lookalikes INTEGER NOT NULL DEFAULT 0,
+            rules TEXT NOT NULL DEFAULT '', last_used INTEGER, use_count INTEGER NOT NULL DEFAULT 0, salt_title TEXT,
+            UNIQUE(username, title));
+        INSERT INTO services (username, title, pass_num, salt_title) VALUES ('alice', 'gmail', 1, 'gmial');
+        INSERT INTO services (username, title, pass_num, salt_title) VALUES ('alice', 'gmial', 1, 'gmial');
+        INSERT INTO services (username, title, pass_num, salt_title) VALUES ('bob', 'gmial', 1, 'gmial');
+        PRAGMA user_version = 3;").unwrap();
+    drop(legacy);
+
+    let conn = service_db_actions::get_connection(&path).unwrap();
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "gmail").unwrap().derivation_id, "gmial");
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "gmial").unwrap().derivation_id, "gmial#2");
+    assert_eq!(service_db_actions::read_service(&conn, "bob", "gmial").unwrap().derivation_id, "gmial");
+
+    drop(conn);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,77 @@
+//! Tests for searching services
+
+use project_stella::search::{self, Usage};
+use project_stella::service_db_actions::{self, ServiceRecord};
+
+const DAY: i64 = 24 * 60 * 60;
+
+#[test]
+fn queries_match_in_order_ignoring_case() {
+    assert!(search::score("gthb", "github").is_some());
+    assert!(search::score("GitHub", "github").is_some());
+    assert!(search::score("hubgit", "github").is_none());
+    assert!(search::score("githubs", "github").is_none());
+    assert_eq!(search::score("", "github"), Some(0));
+
+    // characters with a meaning in regular expressions are matched as they are
+    for query in ["c++", "(", "[a-z]*", ".", "\\"] {
+        assert!(search::score(query, "bank").is_none());
+    }
+    assert!(search::score("c++", "c++ forum").is_some());
+    assert!(search::score("(", "work (old)").is_some());
+}
+
+#[test]
+fn better_matches_score_higher() {
+    let score = |query, text| search::score(query, text).unwrap();
+    // consecutive characters beat scattered ones
+    assert!(score("git", "github") > score("git", "good-items-tracker"));
+    // starts of words beat the middle of words
+    assert!(score("bank", "my-bank") > score("bank", "embankment"));
+    assert!(score("mb", "my-bank") > score("mb", "gumball"));
+}
+
+#[test]
+fn ranking_breaks_ties_with_usage() {
+    let now = 1_700_000_000;
+    let never = Usage::default();
+    let candidates = [
+        ("gitlab", never),
+        ("github", Usage { last_used: Some(now - DAY), count: 40 }),
+        ("git", never),
+        ("gmail", never)
+    ];
+    let ranked = search::rank("git", candidates.iter().map(|(title, usage)| (*title, *title, *usage)), now);
+    // the exact title comes first, the service used often and lately beats the unused one
+    assert_eq!(ranked, vec!["git", "github", "gitlab"]);
+
+    // without usage, shorter titles and then titles in order win
+    let ranked = search::rank("g", ["gmail", "github", "gitlab"].map(|title| (title, title, never)), now);
+    assert_eq!(ranked, vec!["gmail", "github", "gitlab"]);
+
+    // recent use counts more than use long ago
+    let recent = Usage { last_used: Some(now - DAY), count: 1 };
+    let old = Usage { last_used: Some(now - 365 * DAY), count: 1 };
+    assert!(search::usage_bonus(recent, now) > search::usage_bonus(old, now));
+    assert!(search::usage_bonus(old, now) > search::usage_bonus(never, now));
+}
+
+#[test]
+fn uses_are_recorded_and_kept_when_the_recipe_changes() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    let mut record = ServiceRecord::new("github", 1);
+    service_db_actions::update_db(&conn, "alice", &record).unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("gitlab", 1)).unwrap();
+    assert_eq!(service_db_actions::read_usage(&conn, "alice").unwrap()["github"], Usage::default());
+
+    service_db_actions::record_use(&conn, "alice", "github", 100).unwrap();
+    service_db_actions::record_use(&conn, "alice", "github", 200).unwrap();
+    record.pass_num = 2;
+    service_db_actions::update_db(&conn, "alice", &record).unwrap();
+
+    let usage = service_db_actions::read_usage(&conn, "alice").unwrap();
+    assert_eq!(usage["github"], Usage { last_used: Some(200), count: 2 });
+    assert_eq!(usage["gitlab"], Usage::default());
+    assert_eq!(service_db_actions::read_pass_num(&conn, "alice", "github"), 2);
+    assert!(service_db_actions::read_usage(&conn, "bob").unwrap().is_empty());
+}
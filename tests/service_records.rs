@@ -0,0 +1,334 @@
+//! Tests for the service records database
+
+use rusqlite::Connection;
+use project_stella::{Algorithm, Charset, Key, PassphraseSpec, PasswordSpec, Rules};
+use project_stella::search::Usage;
+use project_stella::service_db_actions::{self, Alias, ServiceRecord};
+
+#[test]
+fn services_from_before_algorithm_pinning_use_v1() {
+    let dir = std::env::temp_dir().join(format!("stellar-pinning-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("service_records.db");
+    let path = path.to_str().unwrap();
+
+    // the table as created by builds without the algorithm column
+    let legacy = Connection::open(path).unwrap();
+    legacy.execute("CREATE TABLE services(title TEXT NOT NULL UNIQUE, pass_num INTEGER)", ()).unwrap();
+    legacy.execute("INSERT INTO services (title, pass_num) VALUES ('github', 2)", ()).unwrap();
+    drop(legacy);
+
+    // old services belong to no user until they are assigned
+    let conn = service_db_actions::get_connection(path).unwrap();
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "github"), None);
+    assert_eq!(service_db_actions::count_unowned(&conn).unwrap(), 1);
+    assert!(service_db_actions::is_unowned(&conn, "GitHub").unwrap());
+    assert!(!service_db_actions::is_unowned(&conn, "gitlab").unwrap());
+    assert_eq!(service_db_actions::assign_unowned(&conn, "alice").unwrap(), 1);
+    assert!(!service_db_actions::is_unowned(&conn, "github").unwrap());
+    assert_eq!(service_db_actions::count_unowned(&conn).unwrap(), 0);
+    let record = service_db_actions::read_service(&conn, "alice", "github").unwrap();
+    assert_eq!(record.pass_num, 2);
+    assert_eq!(record.spec.algorithm, Algorithm::V1);
+    assert_eq!(record.spec.length, 16);
+    assert_eq!(record.spec.key, Key::default());
+    assert_eq!(record.spec.charset, Charset::default());
+
+    // new services are pinned to the newest algorithm and stay pinned
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("gitlab", 1)).unwrap();
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "gitlab").unwrap().spec.algorithm, Algorithm::LATEST);
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "github").unwrap().spec.algorithm, Algorithm::V1);
+
+    drop(conn);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn recipe_round_trips() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    let mut record = ServiceRecord::new("bank", 3);
+    record.spec.length = 24;
+    record.spec.key = Key::from_classes("uln").unwrap();
+    record.spec.algorithm = Algorithm::V1;
+    service_db_actions::update_db(&conn, "alice", &record).unwrap();
+
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "bank"), Some(record.clone()));
+    assert_eq!(service_db_actions::read_all_services(&conn, "alice").unwrap(), vec![record.clone()]);
+
+    record.spec.algorithm = Algorithm::V2;
+    record.spec.rules = Rules::parse("n>=2,s<=3,repeat<=2,letter-first").unwrap();
+    service_db_actions::update_db(&conn, "alice", &record).unwrap();
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "bank"), Some(record));
+}
+
+#[test]
+fn passphrase_recipe_round_trips() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    for separator in [Some('-'), Some(' '), None] {
+        let mut record = ServiceRecord::new("wifi", 1);
+        record.spec = PasswordSpec::passphrase(7, PassphraseSpec { separator, capitalize: true, digit: true });
+        service_db_actions::update_db(&conn, "alice", &record).unwrap();
+
+        assert_eq!(service_db_actions::read_service(&conn, "alice", "wifi"), Some(record));
+    }
+}
+
+#[test]
+fn charset_round_trips() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    let mut record = ServiceRecord::new("bank", 1);
+    record.spec.charset.set_symbols("!#$%'\"").unwrap();
+    record.spec.charset.set_excluded("xyz~").unwrap();
+    record.spec.charset.no_lookalikes = true;
+    service_db_actions::update_db(&conn, "alice", &record).unwrap();
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "bank"), Some(record.clone()));
+
+    record.spec.charset.set_symbols("").unwrap();
+    service_db_actions::update_db(&conn, "alice", &record).unwrap();
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "bank"), Some(record));
+}
+
+#[test]
+fn services_belong_to_users() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("github", 2)).unwrap();
+    service_db_actions::update_db(&conn, "bob", &ServiceRecord::new("github", 5)).unwrap();
+    service_db_actions::update_db(&conn, "bob", &ServiceRecord::new("gitlab", 1)).unwrap();
+
+    assert_eq!(service_db_actions::read_pass_num(&conn, "alice", "github"), 2);
+    assert_eq!(service_db_actions::read_pass_num(&conn, "bob", "github"), 5);
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "gitlab"), None);
+    assert_eq!(service_db_actions::read_all_rows(&conn, "alice").unwrap(), vec!["github".to_string()]);
+    assert_eq!(service_db_actions::read_all_services(&conn, "bob").unwrap().len(), 2);
+    assert!(service_db_actions::read_all_rows(&conn, "carol").unwrap().is_empty());
+}
+
+#[test]
+fn assigning_keeps_the_records_of_the_user() {
+    let dir = std::env::temp_dir().join(format!("stellar-owners-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("service_records.db");
+    let path = path.to_str().unwrap();
+
+    let legacy = Connection::open(path).unwrap();
+    legacy.execute("CREATE TABLE services(title TEXT NOT NULL UNIQUE, pass_num INTEGER)", ()).unwrap();
+    legacy.execute("INSERT INTO services (title, pass_num) VALUES ('github', 2), ('netflix', 3)", ()).unwrap();
+    drop(legacy);
+
+    let conn = service_db_actions::get_connection(path).unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("github", 7)).unwrap();
+    assert_eq!(service_db_actions::assign_unowned(&conn, "alice").unwrap(), 1);
+    assert_eq!(service_db_actions::read_pass_num(&conn, "alice", "github"), 7);
+    assert_eq!(service_db_actions::read_pass_num(&conn, "alice", "netflix"), 3);
+
+    // the clashing service is left for another user
+    assert_eq!(service_db_actions::count_unowned(&conn).unwrap(), 1);
+    assert_eq!(service_db_actions::assign_unowned(&conn, "bob").unwrap(), 1);
+    assert_eq!(service_db_actions::read_pass_num(&conn, "bob", "github"), 2);
+
+    drop(conn);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn renamed_services_keep_their_salt() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("gmial", 2)).unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("github", 1)).unwrap();
+    service_db_actions::add_alias(&conn, "alice", "mail", "gmial").unwrap();
+
+    service_db_actions::rename_service(&conn, "alice", "gmial", "gmail").unwrap();
+    let record = service_db_actions::read_service(&conn, "alice", "gmail").unwrap();
+    assert_eq!(record.derivation_id, "gmial");
+    assert_eq!(record.salt_part(), ServiceRecord::new("gmial", 2).salt_part());
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "gmial"), None);
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "mail").unwrap().title, "gmail");
+
+    // the recipe can still change without losing the salt
+    let mut changed = record.clone();
+    changed.spec.length = 20;
+    service_db_actions::update_db(&conn, "alice", &changed).unwrap();
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "gmail"), Some(changed));
+
+    // titles and aliases in use are refused
+    assert!(service_db_actions::rename_service(&conn, "alice", "gmail", "github").is_err());
+    assert!(service_db_actions::rename_service(&conn, "alice", "gmail", "mail").is_err());
+    assert!(service_db_actions::rename_service(&conn, "alice", "gmail", "").is_err());
+    assert!(service_db_actions::rename_service(&conn, "alice", "nothing", "something").is_err());
+    assert!(service_db_actions::rename_service(&conn, "bob", "gmail", "mail2").is_err());
+}
+
+#[test]
+fn aliases_share_the_recipe() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("gmail", 3)).unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("github", 1)).unwrap();
+
+    service_db_actions::add_alias(&conn, "alice", "google", "gmail").unwrap();
+    // an alias of an alias belongs to the service
+    service_db_actions::add_alias(&conn, "alice", "youtube", "google").unwrap();
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "google"), service_db_actions::read_service(&conn, "alice", "gmail"));
+    assert_eq!(service_db_actions::read_aliases(&conn, "alice").unwrap(), vec![
+        Alias { alias: "google".to_string(), title: "gmail".to_string() },
+        Alias { alias: "youtube".to_string(), title: "gmail".to_string() }
+    ]);
+    assert!(service_db_actions::read_aliases(&conn, "bob").unwrap().is_empty());
+    assert!(service_db_actions::read_service(&conn, "bob", "google").is_none());
+
+    assert!(service_db_actions::add_alias(&conn, "alice", "github", "gmail").is_err());
+    assert!(service_db_actions::add_alias(&conn, "alice", "google", "github").is_err());
+    assert!(service_db_actions::add_alias(&conn, "alice", "hub", "nothing").is_err());
+
+    service_db_actions::remove_alias(&conn, "alice", "youtube").unwrap();
+    assert!(service_db_actions::remove_alias(&conn, "alice", "youtube").is_err());
+    assert!(service_db_actions::read_service(&conn, "alice", "youtube").is_none());
+
+    // deleting a service forgets its aliases
+    service_db_actions::delete_service(&conn, "alice", "gmail").unwrap();
+    assert!(service_db_actions::read_service(&conn, "alice", "google").is_none());
+    assert!(service_db_actions::read_aliases(&conn, "alice").unwrap().is_empty());
+    assert!(service_db_actions::delete_service(&conn, "alice", "gmail").is_err());
+    assert_eq!(service_db_actions::read_all_rows(&conn, "alice").unwrap(), vec!["github".to_string()]);
+}
+
+#[test]
+fn merged_duplicates_become_aliases() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("gmail", 3)).unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("gmail.com", 1)).unwrap();
+    service_db_actions::add_alias(&conn, "alice", "mail", "gmail.com").unwrap();
+    service_db_actions::record_use(&conn, "alice", "gmail", 100).unwrap();
+    service_db_actions::record_use(&conn, "alice", "gmail.com", 200).unwrap();
+    service_db_actions::record_use(&conn, "alice", "gmail.com", 300).unwrap();
+
+    assert!(service_db_actions::merge_services(&conn, "alice", "gmail", "gmail").is_err());
+    assert!(service_db_actions::merge_services(&conn, "alice", "gmail", "nothing").is_err());
+    service_db_actions::merge_services(&conn, "alice", "gmail.com", "gmail").unwrap();
+
+    let gmail = service_db_actions::read_service(&conn, "alice", "gmail").unwrap();
+    assert_eq!(gmail.pass_num, 3);
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "gmail.com"), Some(gmail.clone()));
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "mail"), Some(gmail));
+    assert_eq!(service_db_actions::read_all_rows(&conn, "alice").unwrap(), vec!["gmail".to_string()]);
+    let usage = service_db_actions::read_usage(&conn, "alice").unwrap();
+    assert_eq!(usage["gmail"], Usage { last_used: Some(300), count: 3 });
+}
+
+#[test]
+fn titles_keep_their_case() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    let record = ServiceRecord::new("GitHub", 1);
+    assert_eq!(record.derivation_id, "github");
+    assert_eq!(record.salt_part(), ServiceRecord::new("github", 1).salt_part());
+    service_db_actions::update_db(&conn, "alice", &record).unwrap();
+
+    // the title is shown as typed but found in any case
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "github"), Some(record.clone()));
+    assert_eq!(service_db_actions::read_all_rows(&conn, "alice").unwrap(), vec!["GitHub".to_string()]);
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("GITHUB", 2)).unwrap();
+    assert_eq!(service_db_actions::read_all_rows(&conn, "alice").unwrap(), vec!["GitHub".to_string()]);
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "GitHub").unwrap().pass_num, 2);
+    assert!(service_db_actions::add_alias(&conn, "alice", "GITHUB", "github").is_err());
+
+    // the case can be changed without changing the password
+    service_db_actions::rename_service(&conn, "alice", "github", "Github").unwrap();
+    let renamed = service_db_actions::read_service(&conn, "alice", "GITHUB").unwrap();
+    assert_eq!(renamed.title, "Github");
+    assert_eq!(renamed.salt_part(), ServiceRecord::new("github", 2).salt_part());
+}
+
+#[test]
+fn notes_and_tags_round_trip() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    let mut record = ServiceRecord::new("bank", 1);
+    record.notes = "account 1234, the old card".to_string();
+    record.tags = vec!["money".to_string(), "work".to_string()];
+    service_db_actions::update_db(&conn, "alice", &record).unwrap();
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "bank"), Some(record.clone()));
+
+    let tags = service_db_actions::parse_tags(" finance,, Money ,money, 2fa");
+    assert_eq!(tags, vec!["finance".to_string(), "Money".to_string(), "2fa".to_string()]);
+    service_db_actions::update_details(&conn, "alice", "bank", "", &tags).unwrap();
+    let changed = service_db_actions::read_service(&conn, "alice", "bank").unwrap();
+    assert_eq!(changed.notes, "");
+    assert_eq!(changed.tags, tags);
+    assert_eq!(changed.salt_part(), record.salt_part());
+    assert!(service_db_actions::update_details(&conn, "alice", "nothing", "", &[]).is_err());
+    assert!(service_db_actions::update_details(&conn, "bob", "bank", "", &[]).is_err());
+}
+
+#[test]
+fn derivation_ids_survive_the_migration() {
+    let dir = std::env::temp_dir().join(format!("stellar-derivation-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("service_records.db");
+    let path = path.to_str().unwrap();
+
+    // services of schema version 3, one of them renamed
+    service_db_actions::get_connection(path).unwrap();
+    let legacy = Connection::open(path).unwrap();
+    legacy.execute_batch("DROP TABLE services; DROP TABLE aliases;
+        CREATE TABLE services(username TEXT, title TEXT NOT NULL, pass_num INTEGER, algorithm INTEGER NOT NULL DEFAULT 1,
+            length INTEGER NOT NULL DEFAULT 16, classes TEXT NOT NULL DEFAULT 'ulns', mode TEXT NOT NULL DEFAULT 'password',
+            separator TEXT NOT NULL DEFAULT '-', capitalize INTEGER NOT NULL DEFAULT 0, digit INTEGER NOT NULL DEFAULT 0,
+            symbols TEXT, excluded TEXT NOT NULL DEFAULT '', no_lookalikes INTEGER NOT NULL DEFAULT 0,
+            rules TEXT NOT NULL DEFAULT '', last_used INTEGER, use_count INTEGER NOT NULL DEFAULT 0, salt_title TEXT,
+            UNIQUE(username, title));
+        CREATE TABLE aliases(username TEXT NOT NULL, alias TEXT NOT NULL, title TEXT NOT NULL, UNIQUE(username, alias));
+        INSERT INTO services (username, title, pass_num, use_count, salt_title) VALUES ('alice', 'gmail', 2, 5, 'gmial');
+        INSERT INTO services (username, title, pass_num, salt_title) VALUES ('alice', 'github', 1, 'github');
+        INSERT INTO aliases VALUES ('alice', 'mail', 'gmail');
+        PRAGMA user_version = 3;").unwrap();
+    drop(legacy);
+
+    let conn = service_db_actions::get_connection(path).unwrap();
+    let gmail = service_db_actions::read_service(&conn, "alice", "GMail").unwrap();
+    assert_eq!(gmail.derivation_id, "gmial");
+    assert_eq!(gmail.salt_part(), ServiceRecord::new("gmial", 2).salt_part());
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "Mail"), Some(gmail));
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "github").unwrap().salt_part(), "github1");
+    assert_eq!(service_db_actions::read_usage(&conn, "alice").unwrap()["gmail"].count, 5);
+
+    drop(conn);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn services_are_not_merged_into_themselves() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("gmail", 3)).unwrap();
+    service_db_actions::add_alias(&conn, "alice", "google", "gmail").unwrap();
+    let record = service_db_actions::read_service(&conn, "alice", "gmail").unwrap();
+
+    assert!(service_db_actions::merge_services(&conn, "alice", "Gmail", "gmail").is_err());
+    assert!(service_db_actions::merge_services(&conn, "alice", "gmail", "GMAIL").is_err());
+    assert!(service_db_actions::merge_services(&conn, "alice", "gmail", "google").is_err());
+    assert!(service_db_actions::merge_services(&conn, "alice", "GMail", "Google").is_err());
+    assert_eq!(service_db_actions::read_service(&conn, "alice", "gmail"), Some(record.clone()));
+    assert_eq!(service_db_actions::read_all_services(&conn, "alice").unwrap(), vec![record]);
+    assert_eq!(service_db_actions::read_aliases(&conn, "alice").unwrap(), vec![
+        Alias { alias: "google".to_string(), title: "gmail".to_string() }
+    ]);
+}
+
+#[test]
+fn re_added_titles_get_their_own_password() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("gmial", 1)).unwrap();
+    service_db_actions::rename_service(&conn, "alice", "gmial", "gmail").unwrap();
+    let gmail = service_db_actions::read_service(&conn, "alice", "gmail").unwrap();
+
+    // the old title is still the derivation id of the renamed service
+    assert!(service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("gmial", 1)).is_err());
+    assert_eq!(service_db_actions::read_all_rows(&conn, "alice").unwrap(), vec!["gmail".to_string()]);
+
+    let mut gmial = ServiceRecord::new("Gmial", 1);
+    gmial.derivation_id = service_db_actions::unused_derivation_id(&conn, "alice", "Gmial").unwrap();
+    service_db_actions::update_db(&conn, "alice", &gmial).unwrap();
+    let gmial = service_db_actions::read_service(&conn, "alice", "gmial").unwrap();
+    assert_ne!(gmial.salt_part(), gmail.salt_part());
+    assert_eq!(service_db_actions::unused_derivation_id(&conn, "alice", "GMIAL").unwrap(), "gmial#3");
+
+    // other users are not in the way
+    assert_eq!(service_db_actions::unused_derivation_id(&conn, "bob", "gmial").unwrap(), "gmial");
+}
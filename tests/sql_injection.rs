@@ -0,0 +1,78 @@
+//! Regression tests making sure hostile usernames and service titles are
+//! stored as plain data and never interpreted as SQL.
+
+use project_stella::auth;
+use project_stella::service_db_actions::{self, ServiceRecord};
+
+/// Usernames and titles that broke (or would have broken) string-formatted queries
+const HOSTILE: [&str; 8] = [
+    "x\", 99) --",
+    "\"",
+    "'",
+    "a\"b'c",
+    "\"; DROP TABLE services; --",
+    "\"; DROP TABLE auth; --",
+    "\" OR \"1\"=\"1",
+    "title with spaces -- and a comment",
+];
+
+#[test]
+fn hostile_usernames_round_trip() {
+    let conn = auth::get_connection(":memory:").unwrap();
+    assert!(auth::add_user(&conn, "alice", "alice password"));
+
+    for username in HOSTILE {
+        assert!(!auth::user_exists(&conn, username), "{}", username);
+        assert!(auth::add_user(&conn, username, "hostile password"), "{}", username);
+        assert!(auth::user_exists(&conn, username), "{}", username);
+        assert!(auth::verify_user(&conn, username, "hostile password"), "{}", username);
+        assert!(!auth::verify_user(&conn, username, "wrong password"), "{}", username);
+        assert!(!auth::add_user(&conn, username, "other password"), "{}", username);
+    }
+
+    // the unrelated user is untouched
+    assert!(auth::verify_user(&conn, "alice", "alice password"));
+    assert!(!auth::verify_user(&conn, "alice", "hostile password"));
+}
+
+#[test]
+fn hostile_username_does_not_match_other_users() {
+    let conn = auth::get_connection(":memory:").unwrap();
+    assert!(auth::add_user(&conn, "alice", "alice password"));
+
+    assert!(!auth::user_exists(&conn, "\" OR \"1\"=\"1"));
+    assert!(!auth::verify_user(&conn, "\" OR \"1\"=\"1", "alice password"));
+    assert!(!auth::verify_user(&conn, "alice\" --", "alice password"));
+}
+
+#[test]
+fn hostile_titles_round_trip() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("github", 3)).unwrap();
+
+    for (i, title) in HOSTILE.iter().enumerate() {
+        let pass_num = i as u8 + 10;
+        assert_eq!(service_db_actions::read_pass_num(&conn, "alice", title), 1, "{}", title);
+        let record = ServiceRecord::new(title, pass_num);
+        service_db_actions::update_db(&conn, "alice", &record).unwrap();
+        assert_eq!(service_db_actions::read_pass_num(&conn, "alice", title), pass_num, "{}", title);
+        assert_eq!(service_db_actions::read_service(&conn, "alice", title), Some(record), "{}", title);
+    }
+
+    let services = service_db_actions::read_all_services(&conn, "alice").unwrap();
+    assert_eq!(services.len(), HOSTILE.len() + 1);
+    for (i, title) in HOSTILE.iter().enumerate() {
+        assert!(services.contains(&ServiceRecord::new(title, i as u8 + 10)), "{}", title);
+    }
+    assert!(services.contains(&ServiceRecord::new("github", 3)));
+}
+
+#[test]
+fn injected_pass_num_is_not_stored() {
+    let conn = service_db_actions::get_connection(":memory:").unwrap();
+    service_db_actions::update_db(&conn, "alice", &ServiceRecord::new("x\", 99) --", 2)).unwrap();
+
+    assert_eq!(service_db_actions::read_pass_num(&conn, "alice", "x"), 1);
+    assert_eq!(service_db_actions::read_pass_num(&conn, "alice", "x\", 99) --"), 2);
+    assert_eq!(service_db_actions::read_all_rows(&conn, "alice").unwrap(), vec!["x\", 99) --".to_string()]);
+}
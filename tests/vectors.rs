@@ -0,0 +1,39 @@
+//! Replays the frozen test vectors of every generation algorithm. A failure
+//! here means passwords generated by earlier builds no longer reproduce.
+
+use project_stella::{Algorithm, Generator, Key, PasswordSpec};
+
+/// Checks every vector of a file in `tests/vectors`. Salts made of several
+/// parts separate them with `|`.
+fn check_vectors(vectors: &str, algorithm: Algorithm) {
+    let mut checked = 0;
+    for line in vectors.lines().filter(|line| !line.starts_with('#')) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        assert_eq!(fields.len(), 5, "malformed vector: {:?}", line);
+
+        let mut spec = PasswordSpec::new(fields[2].parse().unwrap(), Key::from_classes(fields[3]).unwrap());
+        spec.algorithm = algorithm;
+        let mut master = fields[0].to_string();
+        let salt_parts: Vec<&str> = fields[1].split('|').collect();
+        let pass = Generator::new().generate(&mut master, &salt_parts, &spec).unwrap();
+
+        assert_eq!(pass, fields[4], "vector: {:?}", line);
+        checked += 1;
+    }
+    assert!(checked > 0);
+}
+
+#[test]
+fn v1_vectors() {
+    check_vectors(include_str!("vectors/v1.txt"), Algorithm::V1);
+}
+
+#[test]
+fn v2_vectors() {
+    check_vectors(include_str!("vectors/v2.txt"), Algorithm::V2);
+}
+
+#[test]
+fn v3_vectors() {
+    check_vectors(include_str!("vectors/v3.txt"), Algorithm::V3);
+}